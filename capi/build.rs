@@ -0,0 +1,17 @@
+use std::{env, path::PathBuf};
+
+// The header is generated into `OUT_DIR` on every build. The one in `include` is checked in, so it's only
+// updated when `TRUSTRL_UPDATE_HEADER` is set.
+fn main() {
+    let crate_dir = env::var("CARGO_MANIFEST_DIR").expect("no manifest directory");
+    let out_dir = PathBuf::from(env::var("OUT_DIR").expect("no output directory"));
+    let config = cbindgen::Config::from_file(format!("{crate_dir}/cbindgen.toml")).expect("invalid cbindgen config");
+    let bindings = cbindgen::generate_with_config(&crate_dir, config).expect("generating bindings failed");
+    bindings.write_to_file(out_dir.join("trustrl.h"));
+    if env::var_os("TRUSTRL_UPDATE_HEADER").is_some() {
+        bindings.write_to_file(format!("{crate_dir}/include/trustrl.h"));
+    }
+    println!("cargo:rerun-if-changed=src");
+    println!("cargo:rerun-if-changed=cbindgen.toml");
+    println!("cargo:rerun-if-env-changed=TRUSTRL_UPDATE_HEADER");
+}
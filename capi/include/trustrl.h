@@ -0,0 +1,155 @@
+/* This file is generated by cbindgen. Run `TRUSTRL_UPDATE_HEADER=1 cargo build -p trustrl-capi` to update it. */
+
+#ifndef TRUSTRL_H
+#define TRUSTRL_H
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+// The result of a call.
+typedef enum TrustrlStatus {
+  // The call succeeded.
+  TRUSTRL_STATUS_OK = 0,
+  // An argument was null or not valid UTF-8.
+  TRUSTRL_STATUS_INVALID_ARGUMENT = 1,
+  // A URL couldn't be parsed.
+  TRUSTRL_STATUS_INVALID_URL = 2,
+  // A pipeline definition is invalid.
+  TRUSTRL_STATUS_INVALID_PIPELINE = 3,
+  // Applying a transformation failed.
+  TRUSTRL_STATUS_TRANSFORM_FAILED = 4,
+  // Rendering a URL failed.
+  TRUSTRL_STATUS_RENDER_FAILED = 5,
+  // An unexpected internal error happened.
+  TRUSTRL_STATUS_INTERNAL_ERROR = 6,
+  // A template is invalid.
+  TRUSTRL_STATUS_INVALID_TEMPLATE = 7,
+} TrustrlStatus;
+
+// A list of transformations to apply on URLs.
+typedef struct TrustrlPipeline TrustrlPipeline;
+
+// Renders URLs, either using a template or as JSON objects.
+typedef struct TrustrlRenderer TrustrlRenderer;
+
+// A parsed URL.
+typedef struct TrustrlUrl TrustrlUrl;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// Parse a URL.
+//
+// URLs without a scheme are assumed to use `http`. On success, `url` points to a new URL that must be
+// released using [trustrl_url_free].
+//
+// # Safety
+//
+// `input` must be a valid NUL terminated string, `url` must be a valid pointer and `error` must be either
+// null or a valid pointer.
+enum TrustrlStatus trustrl_url_parse(const char *input,
+                                     struct TrustrlUrl **url,
+                                     char **error);
+
+// Get the string representation of a URL.
+//
+// The returned string must be released using [trustrl_string_free]. Returns null if `url` is null.
+//
+// # Safety
+//
+// `url` must be either null or a URL created by this API.
+char *trustrl_url_to_string(const struct TrustrlUrl *url);
+
+// Release a URL.
+//
+// # Safety
+//
+// `url` must be either null or a URL created by this API that wasn't released yet.
+void trustrl_url_free(struct TrustrlUrl *url);
+
+// Construct a pipeline out of a JSON array of transformations.
+//
+// Transformations use the same format as requests in `trustrl serve` mode, e.g.
+// `[{"set_scheme": "https"}, "sort_query_string"]`. On success, `pipeline` points to a new pipeline that
+// must be released using [trustrl_pipeline_free].
+//
+// # Safety
+//
+// `transformations` must be a valid NUL terminated string, `pipeline` must be a valid pointer and `error`
+// must be either null or a valid pointer.
+enum TrustrlStatus trustrl_pipeline_new(const char *transformations,
+                                        struct TrustrlPipeline **pipeline,
+                                        char **error);
+
+// Apply every transformation in a pipeline on a URL, in order.
+//
+// The URL is only modified if every transformation succeeds.
+//
+// # Safety
+//
+// `pipeline` and `url` must be objects created by this API and `error` must be either null or a valid
+// pointer.
+enum TrustrlStatus trustrl_pipeline_apply(const struct TrustrlPipeline *pipeline,
+                                          struct TrustrlUrl *url,
+                                          char **error);
+
+// Release a pipeline.
+//
+// # Safety
+//
+// `pipeline` must be either null or a pipeline created by this API that wasn't released yet.
+void trustrl_pipeline_free(struct TrustrlPipeline *pipeline);
+
+// Construct a renderer that uses a template, like `{scheme}://{host}{path}`.
+//
+// On success, `renderer` points to a new renderer that must be released using [trustrl_renderer_free]. If the
+// template is malformed or uses unknown keys, [TrustrlStatus::InvalidTemplate] is returned.
+//
+// # Safety
+//
+// `template` must be a valid NUL terminated string, `renderer` must be a valid pointer and `error` must
+// be either null or a valid pointer.
+enum TrustrlStatus trustrl_renderer_new_template(const char *template_,
+                                                 struct TrustrlRenderer **renderer,
+                                                 char **error);
+
+// Construct a renderer that renders URLs as JSON objects.
+//
+// The returned renderer must be released using [trustrl_renderer_free].
+struct TrustrlRenderer *trustrl_renderer_new_json(void);
+
+// Render a URL.
+//
+// On success, `output` points to the rendered URL, which must be released using [trustrl_string_free].
+//
+// # Safety
+//
+// `renderer` and `url` must be objects created by this API, `output` must be a valid pointer and `error`
+// must be either null or a valid pointer.
+enum TrustrlStatus trustrl_render(const struct TrustrlRenderer *renderer,
+                                  const struct TrustrlUrl *url,
+                                  char **output,
+                                  char **error);
+
+// Release a renderer.
+//
+// # Safety
+//
+// `renderer` must be either null or a renderer created by this API that wasn't released yet.
+void trustrl_renderer_free(struct TrustrlRenderer *renderer);
+
+// Release a string returned by this API.
+//
+// # Safety
+//
+// `string` must be either null or a string returned by this API that wasn't released yet.
+void trustrl_string_free(char *string);
+
+#ifdef __cplusplus
+} // extern "C"
+#endif // __cplusplus
+
+#endif /* TRUSTRL_H */
@@ -0,0 +1,437 @@
+//! C API for `trustrl`.
+//!
+//! Every object is exposed as an opaque handle that's created by a `*_new` or `*_parse` function and must be
+//! released using the matching `*_free` function. Functions that can fail return a [TrustrlStatus] and,
+//! if the `error` argument isn't null, store a message describing the failure in it. Every string returned
+//! by this API, including error messages, must be released using [trustrl_string_free].
+//!
+//! The header for this API is in `include/trustrl.h`. It's generated by cbindgen, and building this crate with
+//! `TRUSTRL_UPDATE_HEADER` set updates it.
+
+#![deny(missing_docs)]
+
+use std::{
+    ffi::{c_char, CStr, CString},
+    panic::{self, AssertUnwindSafe},
+    ptr,
+};
+use trustrl::{
+    parse_url,
+    service::{ServiceError, TransformationSpec},
+    transform, Fields, UrlRenderer, UrlTemplate, UrlTransformation,
+};
+
+/// The result of a call.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TrustrlStatus {
+    /// The call succeeded.
+    Ok = 0,
+
+    /// An argument was null or not valid UTF-8.
+    InvalidArgument = 1,
+
+    /// A URL couldn't be parsed.
+    InvalidUrl = 2,
+
+    /// A pipeline definition is invalid.
+    InvalidPipeline = 3,
+
+    /// Applying a transformation failed.
+    TransformFailed = 4,
+
+    /// Rendering a URL failed.
+    RenderFailed = 5,
+
+    /// An unexpected internal error happened.
+    InternalError = 6,
+
+    /// A template is invalid.
+    InvalidTemplate = 7,
+}
+
+/// A parsed URL.
+pub struct TrustrlUrl {
+    url: trustrl::Url,
+}
+
+/// A list of transformations to apply on URLs.
+pub struct TrustrlPipeline {
+    // Declared first so it's dropped before the specs it borrows from.
+    transformations: Vec<UrlTransformation<'static>>,
+    _specs: Vec<TransformationSpec>,
+}
+
+impl TrustrlPipeline {
+    fn new(specs: Vec<TransformationSpec>) -> Result<Self, ServiceError> {
+        let transformations = specs.iter().map(TransformationSpec::build).collect::<Result<Vec<_>, _>>()?;
+        // SAFETY: transformations only borrow the heap allocated strings owned by the specs, which stay put when
+        // the specs are moved and are never modified nor dropped while the transformations are alive.
+        let transformations = unsafe {
+            std::mem::transmute::<Vec<UrlTransformation<'_>>, Vec<UrlTransformation<'static>>>(transformations)
+        };
+        Ok(Self { transformations, _specs: specs })
+    }
+}
+
+/// Renders URLs, either using a template or as JSON objects.
+pub struct TrustrlRenderer {
+    template: Option<String>,
+}
+
+impl TrustrlRenderer {
+    fn renderer(&self) -> UrlRenderer<'_> {
+        match &self.template {
+            Some(template) => UrlRenderer::templated(template),
+            None => UrlRenderer::json(),
+        }
+    }
+}
+
+unsafe fn read_str<'a>(input: *const c_char) -> Result<&'a str, String> {
+    if input.is_null() {
+        return Err("argument is null".into());
+    }
+    CStr::from_ptr(input).to_str().map_err(|e| format!("argument is not valid UTF-8: {e}"))
+}
+
+fn into_c_string(value: String) -> *mut c_char {
+    // Interior NULs can't be represented, so anything after one is dropped.
+    let value = match CString::new(value) {
+        Ok(value) => value,
+        Err(e) => {
+            let position = e.nul_position();
+            let mut value = e.into_vec();
+            value.truncate(position);
+            CString::new(value).expect("NUL still present")
+        }
+    };
+    value.into_raw()
+}
+
+unsafe fn fail(status: TrustrlStatus, message: String, error: *mut *mut c_char) -> TrustrlStatus {
+    if !error.is_null() {
+        *error = into_c_string(message);
+    }
+    status
+}
+
+unsafe fn store<T>(value: T, output: *mut *mut T) {
+    *output = Box::into_raw(Box::new(value));
+}
+
+// Panics can't unwind into C code, so the body of every exported function is run through one of these.
+fn guard<T>(on_panic: T, body: impl FnOnce() -> T) -> T {
+    panic::catch_unwind(AssertUnwindSafe(body)).unwrap_or(on_panic)
+}
+
+unsafe fn guard_status(error: *mut *mut c_char, body: impl FnOnce() -> TrustrlStatus) -> TrustrlStatus {
+    match panic::catch_unwind(AssertUnwindSafe(body)) {
+        Ok(status) => status,
+        Err(_) => fail(TrustrlStatus::InternalError, "internal error".into(), error),
+    }
+}
+
+macro_rules! check_output {
+    ($output:ident, $error:ident) => {
+        if $output.is_null() {
+            return fail(TrustrlStatus::InvalidArgument, format!("{} is null", stringify!($output)), $error);
+        }
+    };
+}
+
+macro_rules! try_call {
+    ($expression:expr, $status:expr, $error:ident) => {
+        match $expression {
+            Ok(value) => value,
+            Err(e) => return fail($status, e.to_string(), $error),
+        }
+    };
+}
+
+/// Parse a URL.
+///
+/// URLs without a scheme are assumed to use `http`. On success, `url` points to a new URL that must be
+/// released using [trustrl_url_free].
+///
+/// # Safety
+///
+/// `input` must be a valid NUL terminated string, `url` must be a valid pointer and `error` must be either
+/// null or a valid pointer.
+#[no_mangle]
+pub unsafe extern "C" fn trustrl_url_parse(
+    input: *const c_char,
+    url: *mut *mut TrustrlUrl,
+    error: *mut *mut c_char,
+) -> TrustrlStatus {
+    guard_status(error, || {
+        check_output!(url, error);
+        let input = try_call!(read_str(input), TrustrlStatus::InvalidArgument, error);
+        let parsed = try_call!(parse_url(input), TrustrlStatus::InvalidUrl, error);
+        store(TrustrlUrl { url: parsed }, url);
+        TrustrlStatus::Ok
+    })
+}
+
+/// Get the string representation of a URL.
+///
+/// The returned string must be released using [trustrl_string_free]. Returns null if `url` is null.
+///
+/// # Safety
+///
+/// `url` must be either null or a URL created by this API.
+#[no_mangle]
+pub unsafe extern "C" fn trustrl_url_to_string(url: *const TrustrlUrl) -> *mut c_char {
+    guard(ptr::null_mut(), || match url.as_ref() {
+        Some(url) => into_c_string(url.url.to_string()),
+        None => ptr::null_mut(),
+    })
+}
+
+/// Release a URL.
+///
+/// # Safety
+///
+/// `url` must be either null or a URL created by this API that wasn't released yet.
+#[no_mangle]
+pub unsafe extern "C" fn trustrl_url_free(url: *mut TrustrlUrl) {
+    guard((), || {
+        if !url.is_null() {
+            drop(Box::from_raw(url));
+        }
+    })
+}
+
+/// Construct a pipeline out of a JSON array of transformations.
+///
+/// Transformations use the same format as requests in `trustrl serve` mode, e.g.
+/// `[{"set_scheme": "https"}, "sort_query_string"]`. On success, `pipeline` points to a new pipeline that
+/// must be released using [trustrl_pipeline_free].
+///
+/// # Safety
+///
+/// `transformations` must be a valid NUL terminated string, `pipeline` must be a valid pointer and `error`
+/// must be either null or a valid pointer.
+#[no_mangle]
+pub unsafe extern "C" fn trustrl_pipeline_new(
+    transformations: *const c_char,
+    pipeline: *mut *mut TrustrlPipeline,
+    error: *mut *mut c_char,
+) -> TrustrlStatus {
+    guard_status(error, || {
+        check_output!(pipeline, error);
+        let transformations = try_call!(read_str(transformations), TrustrlStatus::InvalidArgument, error);
+        let specs = try_call!(serde_json::from_str(transformations), TrustrlStatus::InvalidPipeline, error);
+        let built = try_call!(TrustrlPipeline::new(specs), TrustrlStatus::InvalidPipeline, error);
+        store(built, pipeline);
+        TrustrlStatus::Ok
+    })
+}
+
+/// Apply every transformation in a pipeline on a URL, in order.
+///
+/// The URL is only modified if every transformation succeeds.
+///
+/// # Safety
+///
+/// `pipeline` and `url` must be objects created by this API and `error` must be either null or a valid
+/// pointer.
+#[no_mangle]
+pub unsafe extern "C" fn trustrl_pipeline_apply(
+    pipeline: *const TrustrlPipeline,
+    url: *mut TrustrlUrl,
+    error: *mut *mut c_char,
+) -> TrustrlStatus {
+    guard_status(error, || {
+        let (pipeline, url) = match (pipeline.as_ref(), url.as_mut()) {
+            (Some(pipeline), Some(url)) => (pipeline, url),
+            _ => return fail(TrustrlStatus::InvalidArgument, "argument is null".into(), error),
+        };
+        let transformed = transform::apply_all(&pipeline.transformations, url.url.clone());
+        url.url = try_call!(transformed, TrustrlStatus::TransformFailed, error);
+        TrustrlStatus::Ok
+    })
+}
+
+/// Release a pipeline.
+///
+/// # Safety
+///
+/// `pipeline` must be either null or a pipeline created by this API that wasn't released yet.
+#[no_mangle]
+pub unsafe extern "C" fn trustrl_pipeline_free(pipeline: *mut TrustrlPipeline) {
+    guard((), || {
+        if !pipeline.is_null() {
+            drop(Box::from_raw(pipeline));
+        }
+    })
+}
+
+/// Construct a renderer that uses a template, like `{scheme}://{host}{path}`.
+///
+/// On success, `renderer` points to a new renderer that must be released using [trustrl_renderer_free]. If the
+/// template is malformed or uses unknown keys, [TrustrlStatus::InvalidTemplate] is returned.
+///
+/// # Safety
+///
+/// `template` must be a valid NUL terminated string, `renderer` must be a valid pointer and `error` must
+/// be either null or a valid pointer.
+#[no_mangle]
+pub unsafe extern "C" fn trustrl_renderer_new_template(
+    template: *const c_char,
+    renderer: *mut *mut TrustrlRenderer,
+    error: *mut *mut c_char,
+) -> TrustrlStatus {
+    guard_status(error, || {
+        check_output!(renderer, error);
+        let template = try_call!(read_str(template), TrustrlStatus::InvalidArgument, error);
+        try_call!(UrlTemplate::new(template).validate::<&str>(&[]), TrustrlStatus::InvalidTemplate, error);
+        store(TrustrlRenderer { template: Some(template.to_string()) }, renderer);
+        TrustrlStatus::Ok
+    })
+}
+
+/// Construct a renderer that renders URLs as JSON objects.
+///
+/// The returned renderer must be released using [trustrl_renderer_free].
+#[no_mangle]
+pub extern "C" fn trustrl_renderer_new_json() -> *mut TrustrlRenderer {
+    guard(ptr::null_mut(), || Box::into_raw(Box::new(TrustrlRenderer { template: None })))
+}
+
+/// Render a URL.
+///
+/// On success, `output` points to the rendered URL, which must be released using [trustrl_string_free].
+///
+/// # Safety
+///
+/// `renderer` and `url` must be objects created by this API, `output` must be a valid pointer and `error`
+/// must be either null or a valid pointer.
+#[no_mangle]
+pub unsafe extern "C" fn trustrl_render(
+    renderer: *const TrustrlRenderer,
+    url: *const TrustrlUrl,
+    output: *mut *mut c_char,
+    error: *mut *mut c_char,
+) -> TrustrlStatus {
+    guard_status(error, || {
+        check_output!(output, error);
+        let (renderer, url) = match (renderer.as_ref(), url.as_ref()) {
+            (Some(renderer), Some(url)) => (renderer, url),
+            _ => return fail(TrustrlStatus::InvalidArgument, "argument is null".into(), error),
+        };
+        let mut rendered = Vec::new();
+        try_call!(
+            renderer.renderer().render_with_fields(&url.url, &Fields::new(), &mut rendered),
+            TrustrlStatus::RenderFailed,
+            error
+        );
+        *output = into_c_string(String::from_utf8_lossy(&rendered).into_owned());
+        TrustrlStatus::Ok
+    })
+}
+
+/// Release a renderer.
+///
+/// # Safety
+///
+/// `renderer` must be either null or a renderer created by this API that wasn't released yet.
+#[no_mangle]
+pub unsafe extern "C" fn trustrl_renderer_free(renderer: *mut TrustrlRenderer) {
+    guard((), || {
+        if !renderer.is_null() {
+            drop(Box::from_raw(renderer));
+        }
+    })
+}
+
+/// Release a string returned by this API.
+///
+/// # Safety
+///
+/// `string` must be either null or a string returned by this API that wasn't released yet.
+#[no_mangle]
+pub unsafe extern "C" fn trustrl_string_free(string: *mut c_char) {
+    guard((), || {
+        if !string.is_null() {
+            drop(CString::from_raw(string));
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn c_string(value: &str) -> CString {
+        CString::new(value).unwrap()
+    }
+
+    unsafe fn take_string(string: *mut c_char) -> String {
+        let output = CStr::from_ptr(string).to_str().unwrap().to_string();
+        trustrl_string_free(string);
+        output
+    }
+
+    #[test]
+    fn pipeline() {
+        unsafe {
+            let mut url = ptr::null_mut();
+            let status = trustrl_url_parse(c_string("example.com/?b=1&a=2").as_ptr(), &mut url, ptr::null_mut());
+            assert_eq!(status, TrustrlStatus::Ok);
+
+            let mut pipeline = ptr::null_mut();
+            let transformations = c_string(r#"[{"set_scheme": "https"}, "sort_query_string"]"#);
+            let status = trustrl_pipeline_new(transformations.as_ptr(), &mut pipeline, ptr::null_mut());
+            assert_eq!(status, TrustrlStatus::Ok);
+            assert_eq!(trustrl_pipeline_apply(pipeline, url, ptr::null_mut()), TrustrlStatus::Ok);
+
+            let mut renderer = ptr::null_mut();
+            let status = trustrl_renderer_new_template(c_string("{url}").as_ptr(), &mut renderer, ptr::null_mut());
+            assert_eq!(status, TrustrlStatus::Ok);
+            let mut output = ptr::null_mut();
+            assert_eq!(trustrl_render(renderer, url, &mut output, ptr::null_mut()), TrustrlStatus::Ok);
+            assert_eq!(take_string(output), "https://example.com/?a=2&b=1");
+
+            trustrl_renderer_free(renderer);
+            trustrl_pipeline_free(pipeline);
+            trustrl_url_free(url);
+        }
+    }
+
+    #[test]
+    fn errors() {
+        unsafe {
+            let mut url = ptr::null_mut();
+            let mut error = ptr::null_mut();
+            let status = trustrl_url_parse(c_string("http://[x").as_ptr(), &mut url, &mut error);
+            assert_eq!(status, TrustrlStatus::InvalidUrl);
+            assert!(url.is_null());
+            assert_eq!(take_string(error), "invalid IPv6 address");
+
+            let mut pipeline = ptr::null_mut();
+            let transformations = c_string(r#"[{"allow_query_string": ["("]}]"#);
+            let status = trustrl_pipeline_new(transformations.as_ptr(), &mut pipeline, ptr::null_mut());
+            assert_eq!(status, TrustrlStatus::InvalidPipeline);
+
+            let mut renderer = ptr::null_mut();
+            let status = trustrl_renderer_new_template(c_string("{nope}").as_ptr(), &mut renderer, &mut error);
+            assert_eq!(status, TrustrlStatus::InvalidTemplate);
+            assert!(renderer.is_null());
+            assert_eq!(take_string(error), "invalid template: unknown key 'nope'");
+
+            let status = trustrl_url_parse(ptr::null(), &mut url, ptr::null_mut());
+            assert_eq!(status, TrustrlStatus::InvalidArgument);
+        }
+    }
+
+    #[test]
+    fn header_up_to_date() {
+        let generated = include_str!(concat!(env!("OUT_DIR"), "/trustrl.h"));
+        let checked_in = include_str!("../include/trustrl.h");
+        assert!(
+            generated == checked_in,
+            "run `TRUSTRL_UPDATE_HEADER=1 cargo build -p trustrl-capi` to update the header"
+        );
+    }
+}
@@ -0,0 +1,48 @@
+//! Input parsing.
+//!
+//! This allows extracting URLs out of inputs that aren't simply a list of URLs, such as web server
+//! access logs.
+
+pub mod delimited;
+pub mod follow;
+pub mod har;
+pub mod jsonl;
+pub mod lines;
+pub mod log;
+pub mod pairs;
+
+use crate::render::Fields;
+use std::{borrow::Cow, io};
+
+/// A URL read from some input along with any extra fields that came with it.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct InputRecord {
+    /// The URL.
+    pub url: String,
+
+    /// Any extra fields associated with this URL.
+    pub fields: Fields,
+}
+
+impl InputRecord {
+    /// Construct a record that only contains a URL.
+    pub fn new<S: Into<String>>(url: S) -> Self {
+        Self { url: url.into(), fields: Fields::new() }
+    }
+}
+
+/// An error during the parsing of an input.
+#[derive(Debug, thiserror::Error)]
+pub enum InputError {
+    /// Reading the input failed.
+    #[error(transparent)]
+    Io(#[from] io::Error),
+
+    /// The input is malformed.
+    #[error("{0}")]
+    Malformed(Cow<'static, str>),
+
+    /// An error at a specific line in the input.
+    #[error("line {0}: {1}")]
+    Line(usize, Box<InputError>),
+}
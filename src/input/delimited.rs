@@ -0,0 +1,195 @@
+//! Delimited file (CSV/TSV) parsing.
+
+use super::InputError;
+use std::{io::Read, str::FromStr};
+
+/// A column in a delimited file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Column {
+    /// A column referenced by its 0-based index.
+    Index(usize),
+
+    /// A column referenced by its name in the header row.
+    Name(String),
+}
+
+impl FromStr for Column {
+    type Err = InputError;
+
+    /// Parse a column, either as a 1-based number or as a name.
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        match input.parse::<usize>() {
+            Ok(0) => Err(InputError::Malformed("column numbers start at 1".into())),
+            Ok(number) => Ok(Self::Index(number - 1)),
+            Err(_) if input.is_empty() => Err(InputError::Malformed("empty column name".into())),
+            Err(_) => Ok(Self::Name(input.to_string())),
+        }
+    }
+}
+
+/// A row in a delimited file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DelimitedRow {
+    /// The line this row starts at.
+    pub line: u64,
+
+    /// The values in every column in this row.
+    pub values: Vec<String>,
+}
+
+/// Reads the rows in a delimited file, such as a CSV or TSV file.
+pub struct DelimitedReader<R: Read> {
+    reader: csv::Reader<R>,
+    headers: Option<Vec<String>>,
+    column: usize,
+}
+
+impl<R: Read> DelimitedReader<R> {
+    /// Construct a new reader whose rows are terminated by either `\n` or `\r\n`.
+    ///
+    /// If `has_headers` is true, the first row is taken as the header row. The column that contains the URL
+    /// can only be referenced by name if there's a header row.
+    pub fn new(reader: R, delimiter: u8, has_headers: bool, column: &Column) -> Result<Self, InputError> {
+        Self::with_terminator(reader, delimiter, b'\n', has_headers, column)
+    }
+
+    /// Construct a new reader whose rows are terminated by the given byte, like NUL.
+    pub fn with_terminator(
+        reader: R,
+        delimiter: u8,
+        terminator: u8,
+        has_headers: bool,
+        column: &Column,
+    ) -> Result<Self, InputError> {
+        let terminator = match terminator {
+            b'\n' => csv::Terminator::CRLF,
+            terminator => csv::Terminator::Any(terminator),
+        };
+        let mut reader = csv::ReaderBuilder::new()
+            .delimiter(delimiter)
+            .terminator(terminator)
+            .has_headers(has_headers)
+            .flexible(true)
+            .from_reader(reader);
+        let headers = match has_headers {
+            true => Some(reader.headers().map_err(from_csv_error)?.iter().map(String::from).collect::<Vec<_>>()),
+            false => None,
+        };
+        let column = match (column, &headers) {
+            (Column::Index(index), _) => *index,
+            (Column::Name(name), Some(headers)) => headers
+                .iter()
+                .position(|header| header == name)
+                .ok_or_else(|| InputError::Malformed(format!("column '{name}' not found").into()))?,
+            (Column::Name(_), None) => {
+                return Err(InputError::Malformed("columns can only be referenced by name if there's a header".into()))
+            }
+        };
+        Ok(Self { reader, headers, column })
+    }
+
+    /// The header row, if any.
+    pub fn headers(&self) -> Option<&[String]> {
+        self.headers.as_deref()
+    }
+
+    /// The 0-based index of the column that contains the URL.
+    pub fn column(&self) -> usize {
+        self.column
+    }
+}
+
+impl<R: Read> Iterator for DelimitedReader<R> {
+    type Item = Result<DelimitedRow, InputError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut record = csv::StringRecord::new();
+        let row = match self.reader.read_record(&mut record) {
+            Ok(false) => return None,
+            Ok(true) => {
+                let line = record.position().map(|position| position.line()).unwrap_or_default();
+                if record.len() <= self.column {
+                    let error = InputError::Malformed(format!("column {} not found", self.column + 1).into());
+                    return Some(Err(InputError::Line(line as usize, error.into())));
+                }
+                Ok(DelimitedRow { line, values: record.iter().map(String::from).collect() })
+            }
+            Err(e) => Err(from_csv_error(e)),
+        };
+        Some(row)
+    }
+}
+
+fn from_csv_error(error: csv::Error) -> InputError {
+    // The error's message already contains the position it happened at.
+    let message = error.to_string();
+    match error.into_kind() {
+        csv::ErrorKind::Io(e) => InputError::Io(e),
+        _ => InputError::Malformed(message.into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    fn read(input: &str, delimiter: u8, has_headers: bool, column: &str) -> Result<Vec<DelimitedRow>, InputError> {
+        let column = column.parse()?;
+        DelimitedReader::new(input.as_bytes(), delimiter, has_headers, &column)?.collect()
+    }
+
+    #[rstest]
+    #[case::number("2", Column::Index(1))]
+    #[case::name("url", Column::Name("url".into()))]
+    fn parse_column(#[case] input: &str, #[case] expected: Column) {
+        assert_eq!(input.parse::<Column>().expect("parse failed"), expected);
+    }
+
+    #[rstest]
+    #[case::zero("0")]
+    #[case::empty("")]
+    fn parse_column_failure(#[case] input: &str) {
+        assert!(input.parse::<Column>().is_err());
+    }
+
+    #[test]
+    fn read_by_name() {
+        let input = "id,url\n1,http://a.com\n2,\"http://b.com/?a=1,2\"\n";
+        let mut reader = DelimitedReader::new(input.as_bytes(), b',', true, &Column::Name("url".into())).unwrap();
+        assert_eq!(reader.headers(), Some(&["id".to_string(), "url".to_string()][..]));
+        assert_eq!(reader.column(), 1);
+
+        let rows: Vec<_> = reader.by_ref().collect::<Result<_, _>>().expect("read failed");
+        assert_eq!(rows[0].values, &["1", "http://a.com"]);
+        assert_eq!(rows[1].values, &["2", "http://b.com/?a=1,2"]);
+        assert_eq!(rows[1].line, 3);
+    }
+
+    #[test]
+    fn read_nul_terminated() {
+        let input = "a,b\0http://x.com/\n,1\0";
+        let rows: Vec<_> = DelimitedReader::with_terminator(input.as_bytes(), b',', b'\0', true, &Column::Index(0))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .expect("read failed");
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].values, &["http://x.com/\n", "1"]);
+    }
+
+    #[test]
+    fn read_tsv_without_headers() {
+        let rows = read("http://a.com\tx\nhttp://b.com\ty\n", b'\t', false, "1").expect("read failed");
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[1].values, &["http://b.com", "y"]);
+    }
+
+    #[rstest]
+    #[case::unknown_name("id,url\n1,a.com\n", true, "potato")]
+    #[case::name_without_headers("id,url\n1,a.com\n", false, "url")]
+    #[case::missing_column("id,url\n1\n", true, "url")]
+    fn read_failure(#[case] input: &str, #[case] has_headers: bool, #[case] column: &str) {
+        let result = read(input, b',', has_headers, column);
+        assert!(result.is_err(), "result was {result:?}");
+    }
+}
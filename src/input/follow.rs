@@ -0,0 +1,216 @@
+//! Reading files as they grow.
+
+use std::{
+    fs::{self, File, Metadata},
+    io::{self, Read, Seek, SeekFrom},
+    path::PathBuf,
+    thread,
+    time::Duration,
+};
+
+// The number of bytes at the start of a file that are compared to tell whether it was rewritten.
+const HEAD_SIZE: u64 = 256;
+
+/// Reads a file as it grows, the way `tail -F` does.
+///
+/// Reaching the end of the file doesn't end the input: reads block until more data is appended to it.
+/// Log rotation is handled as well:
+/// * If the file is replaced by a new one, e.g. after being renamed, the new file is read from the start.
+/// * If the file is truncated, it is read again from the start. A file that's truncated and written past the
+///   point where reading stopped is told apart from one that grew by comparing the first bytes in it.
+pub struct FollowReader {
+    path: PathBuf,
+    file: File,
+    position: u64,
+    // The first bytes in the file, up to the position reading is at, as of the last check.
+    head: Vec<u8>,
+    interval: Duration,
+}
+
+impl FollowReader {
+    /// Open the file in the given path, reading only the data appended to it from now on.
+    pub fn open<P: Into<PathBuf>>(path: P) -> io::Result<Self> {
+        let mut reader = Self::open_from_start(path)?;
+        reader.position = reader.file.seek(SeekFrom::End(0))?;
+        reader.head = reader.read_head()?;
+        Ok(reader)
+    }
+
+    /// Open the file in the given path, reading the data already in it first.
+    pub fn open_from_start<P: Into<PathBuf>>(path: P) -> io::Result<Self> {
+        let path = path.into();
+        let file = File::open(&path)?;
+        Ok(Self { path, file, position: 0, head: Vec::new(), interval: Duration::from_millis(250) })
+    }
+
+    /// Set how long to wait before checking for new data once the end of the file is reached.
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    // Returns true if reading should start over because the file was replaced or truncated.
+    fn check_rotation(&mut self) -> io::Result<bool> {
+        let metadata = match fs::metadata(&self.path) {
+            Ok(metadata) => metadata,
+            // The file was moved away but the new one wasn't created yet.
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(false),
+            Err(e) => return Err(e),
+        };
+        if !is_same_file(&self.file.metadata()?, &metadata) {
+            self.file = match File::open(&self.path) {
+                Ok(file) => file,
+                Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(false),
+                Err(e) => return Err(e),
+            };
+        } else if metadata.len() < self.position || self.read_head()? != self.head {
+            self.file.seek(SeekFrom::Start(0))?;
+        } else {
+            self.head = self.read_head()?;
+            return Ok(false);
+        }
+        self.position = 0;
+        self.head.clear();
+        Ok(true)
+    }
+
+    // Read the first bytes in the file, up to the position reading is at, and go back to that position.
+    fn read_head(&mut self) -> io::Result<Vec<u8>> {
+        let mut head = Vec::new();
+        self.file.seek(SeekFrom::Start(0))?;
+        (&mut self.file).take(self.position.min(HEAD_SIZE)).read_to_end(&mut head)?;
+        self.file.seek(SeekFrom::Start(self.position))?;
+        Ok(head)
+    }
+}
+
+impl Read for FollowReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        loop {
+            let read = self.file.read(buf)?;
+            if read > 0 {
+                let missing = HEAD_SIZE.saturating_sub(self.position).min(read as u64) as usize;
+                self.head.extend_from_slice(&buf[..missing]);
+                self.position += read as u64;
+                return Ok(read);
+            }
+            // Check after waiting so data written meanwhile isn't read before noticing the file was rewritten.
+            thread::sleep(self.interval);
+            self.check_rotation()?;
+        }
+    }
+}
+
+#[cfg(unix)]
+fn is_same_file(lhs: &Metadata, rhs: &Metadata) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    lhs.dev() == rhs.dev() && lhs.ino() == rhs.ino()
+}
+
+// There's no cheap way to tell files apart here, so only truncation is detected.
+#[cfg(not(unix))]
+fn is_same_file(_lhs: &Metadata, _rhs: &Metadata) -> bool {
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{fs::OpenOptions, io::Write};
+
+    struct TestFile(PathBuf);
+
+    impl TestFile {
+        fn new(name: &str) -> Self {
+            let path = std::env::temp_dir().join(format!("trustrl-{}-{name}", std::process::id()));
+            fs::write(&path, "").expect("creating file failed");
+            Self(path)
+        }
+
+        fn append(&self, data: &str) {
+            let mut file = OpenOptions::new().append(true).open(&self.0).expect("opening file failed");
+            file.write_all(data.as_bytes()).expect("write failed");
+        }
+    }
+
+    impl Drop for TestFile {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.0);
+        }
+    }
+
+    fn read(reader: &mut FollowReader, length: usize) -> String {
+        let mut buffer = vec![0; length];
+        reader.read_exact(&mut buffer).expect("read failed");
+        String::from_utf8(buffer).unwrap()
+    }
+
+    fn open(file: &TestFile) -> FollowReader {
+        FollowReader::open_from_start(&file.0).expect("open failed").with_interval(Duration::from_millis(1))
+    }
+
+    #[test]
+    fn from_end() {
+        let file = TestFile::new("end");
+        file.append("a.com\n");
+        let mut reader = FollowReader::open(&file.0).expect("open failed").with_interval(Duration::from_millis(1));
+        file.append("b.com\n");
+        assert_eq!(read(&mut reader, 6), "b.com\n");
+    }
+
+    #[test]
+    fn appended_data() {
+        let file = TestFile::new("appended");
+        file.append("a.com\n");
+        let mut reader = open(&file);
+        assert_eq!(read(&mut reader, 6), "a.com\n");
+
+        file.append("b.com\n");
+        assert_eq!(read(&mut reader, 6), "b.com\n");
+    }
+
+    #[test]
+    fn rotation() {
+        let file = TestFile::new("rotation");
+        let rotated = TestFile(file.0.with_extension("1"));
+        file.append("a.com\n");
+        let mut reader = open(&file);
+        assert_eq!(read(&mut reader, 6), "a.com\n");
+
+        fs::rename(&file.0, &rotated.0).expect("rename failed");
+        fs::write(&file.0, "b.com\n").expect("write failed");
+        assert_eq!(read(&mut reader, 6), "b.com\n");
+    }
+
+    #[test]
+    fn truncation() {
+        let file = TestFile::new("truncation");
+        file.append("a.com\nb.com\n");
+        let mut reader = open(&file);
+        assert_eq!(read(&mut reader, 12), "a.com\nb.com\n");
+
+        fs::write(&file.0, "c.com\n").expect("write failed");
+        assert_eq!(read(&mut reader, 6), "c.com\n");
+    }
+
+    #[test]
+    fn truncation_past_position() {
+        let file = TestFile::new("truncation-past");
+        file.append("a.com\n");
+        let mut reader = open(&file);
+        assert_eq!(read(&mut reader, 6), "a.com\n");
+
+        let path = file.0.clone();
+        let writer = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(50));
+            // Overwritten without truncating it first, so its size never goes down.
+            let mut file = OpenOptions::new().write(true).open(path).expect("opening file failed");
+            file.write_all(b"b.com\nc.com\n").expect("write failed");
+        });
+        assert_eq!(read(&mut reader, 12), "b.com\nc.com\n");
+        writer.join().unwrap();
+    }
+}
@@ -0,0 +1,166 @@
+//! HTTP Archive (HAR) parsing.
+
+use super::{InputError, InputRecord};
+use crate::render::Fields;
+use serde::Deserialize;
+use serde_json::Value;
+use std::io::Read;
+
+/// Parse a HAR document and extract a record out of every entry in it.
+///
+/// Besides the request URL, every record contains the following fields:
+/// * method
+/// * status
+/// * mime_type
+/// * started
+/// * time: the total time the request took, in milliseconds.
+/// * timings: an object containing the time spent in each phase of the request.
+///
+/// # Example
+///
+/// ```
+/// # use trustrl::input::har::parse_har;
+/// # fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// let har = r#"{"log": {"entries": [{"request": {"method": "GET", "url": "https://example.com/"}}]}}"#;
+/// let records = parse_har(har.as_bytes())?;
+/// assert_eq!(records[0].url, "https://example.com/");
+/// assert_eq!(records[0].fields["method"], "GET");
+/// # Ok(())
+/// # }
+/// ```
+pub fn parse_har<R: Read>(reader: R) -> Result<Vec<InputRecord>, InputError> {
+    let har: Har = serde_json::from_reader(reader).map_err(|e| InputError::Malformed(e.to_string().into()))?;
+    let records = har.log.entries.into_iter().map(HarEntry::into_record).collect();
+    Ok(records)
+}
+
+#[derive(Deserialize)]
+struct Har {
+    log: HarLog,
+}
+
+#[derive(Deserialize)]
+struct HarLog {
+    entries: Vec<HarEntry>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct HarEntry {
+    started_date_time: Option<String>,
+    time: Option<f64>,
+    request: HarRequest,
+    response: Option<HarResponse>,
+    timings: Option<Value>,
+}
+
+#[derive(Deserialize)]
+struct HarRequest {
+    method: String,
+    url: String,
+}
+
+#[derive(Deserialize)]
+struct HarResponse {
+    status: Option<i64>,
+    content: Option<HarContent>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct HarContent {
+    mime_type: Option<String>,
+}
+
+impl HarEntry {
+    fn into_record(self) -> InputRecord {
+        // Browsers report a status of 0, and some tools -1, for requests that never got a response.
+        let status = self
+            .response
+            .as_ref()
+            .and_then(|response| response.status)
+            .and_then(|status| u16::try_from(status).ok())
+            .filter(|status| *status != 0);
+        let mime_type =
+            self.response.and_then(|response| response.content).and_then(|content| content.mime_type);
+        let mut fields = Fields::new();
+        fields.insert("method".into(), self.request.method.into());
+        fields.insert("status".into(), status.into());
+        fields.insert("mime_type".into(), mime_type.filter(|mime_type| !mime_type.is_empty()).into());
+        fields.insert("started".into(), self.started_date_time.into());
+        fields.insert("time".into(), self.time.into());
+        fields.insert("timings".into(), self.timings.unwrap_or(Value::Null));
+        InputRecord { url: self.request.url, fields }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+    use serde_json::json;
+
+    const HAR: &str = r#"{
+      "log": {
+        "version": "1.2",
+        "creator": {"name": "WebInspector", "version": "537.36"},
+        "entries": [
+          {
+            "startedDateTime": "2023-04-01T10:00:00.000Z",
+            "time": 120.5,
+            "request": {"method": "GET", "url": "https://example.com/?a=b", "httpVersion": "HTTP/2", "headers": []},
+            "response": {"status": 200, "content": {"size": 10, "mimeType": "text/html"}},
+            "timings": {"blocked": 1, "dns": -1, "wait": 100.5, "receive": 19}
+          },
+          {
+            "request": {"method": "POST", "url": "https://example.com/api"},
+            "response": {"status": 0, "content": {"size": 0, "mimeType": ""}}
+          }
+        ]
+      }
+    }"#;
+
+    #[test]
+    fn parse_entries() {
+        let records = parse_har(HAR.as_bytes()).expect("parse failed");
+        assert_eq!(records.len(), 2);
+
+        let fields = &records[0].fields;
+        assert_eq!(records[0].url, "https://example.com/?a=b");
+        assert_eq!(fields["method"], "GET");
+        assert_eq!(fields["status"], 200);
+        assert_eq!(fields["mime_type"], "text/html");
+        assert_eq!(fields["started"], "2023-04-01T10:00:00.000Z");
+        assert_eq!(fields["time"], 120.5);
+        assert_eq!(fields["timings"]["wait"], 100.5);
+
+        let fields = &records[1].fields;
+        assert_eq!(records[1].url, "https://example.com/api");
+        assert_eq!(fields["method"], "POST");
+        assert_eq!(fields["status"], Value::Null);
+        assert_eq!(fields["mime_type"], Value::Null);
+        assert_eq!(fields["time"], Value::Null);
+    }
+
+    #[rstest]
+    #[case::valid(404, 404.into())]
+    #[case::zero(0, Value::Null)]
+    #[case::negative(-1, Value::Null)]
+    #[case::too_large(70000, Value::Null)]
+    fn lenient_status(#[case] status: i64, #[case] expected: Value) {
+        let entry = json!({"request": {"method": "GET", "url": "a.com"}, "response": {"status": status}});
+        let har = json!({"log": {"entries": [entry]}});
+        let records = parse_har(har.to_string().as_bytes()).expect("parse failed");
+        assert_eq!(records[0].fields["status"], expected);
+    }
+
+    #[rstest]
+    #[case::not_json("potato")]
+    #[case::no_log(r#"{"entries": []}"#)]
+    #[case::no_request(r#"{"log": {"entries": [{"time": 1}]}}"#)]
+    #[case::no_url(r#"{"log": {"entries": [{"request": {"method": "GET"}}]}}"#)]
+    fn parse_failure(#[case] input: &str) {
+        let result = parse_har(input.as_bytes());
+        assert!(result.is_err(), "result was {result:?}");
+    }
+}
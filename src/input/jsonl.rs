@@ -0,0 +1,162 @@
+//! JSON Lines (NDJSON) parsing.
+
+use super::InputError;
+use serde_json::Value;
+use std::str::FromStr;
+
+/// The path to a field within a JSON record.
+///
+/// Paths can be either JSON pointers, like `/request/url`, or dotted paths, like `request.url`. Array
+/// elements are referenced by their index, e.g. `/links/0` or `links.0`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FieldPath {
+    tokens: Vec<String>,
+}
+
+impl FieldPath {
+    /// Get the value this path points to in a record.
+    pub fn get<'a>(&self, record: &'a Value) -> Option<&'a Value> {
+        self.tokens.iter().try_fold(record, |value, token| match value {
+            Value::Object(object) => object.get(token),
+            Value::Array(array) => token.parse::<usize>().ok().and_then(|index| array.get(index)),
+            _ => None,
+        })
+    }
+
+    /// Set the value this path points to in a record.
+    ///
+    /// Any objects missing along the path are created.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use serde_json::json;
+    /// # use trustrl::input::jsonl::FieldPath;
+    /// # fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut record = json!({"request": {"url": "http://example.com"}});
+    /// let path: FieldPath = "request.host".parse()?;
+    /// path.set(&mut record, "example.com".into())?;
+    /// assert_eq!(record, json!({"request": {"url": "http://example.com", "host": "example.com"}}));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn set(&self, record: &mut Value, new_value: Value) -> Result<(), InputError> {
+        let mut value = record;
+        for token in &self.tokens {
+            value = match value {
+                Value::Object(object) => object.entry(token.as_str()).or_insert(Value::Null),
+                Value::Array(array) => match token.parse::<usize>().ok().and_then(|index| array.get_mut(index)) {
+                    Some(value) => value,
+                    None => return Err(InputError::Malformed(format!("array index '{token}' not found").into())),
+                },
+                Value::Null => {
+                    *value = Value::Object(Default::default());
+                    value.as_object_mut().expect("not an object").entry(token.as_str()).or_insert(Value::Null)
+                }
+                _ => return Err(InputError::Malformed(format!("cannot set field '{token}' in a non object").into())),
+            };
+        }
+        *value = new_value;
+        Ok(())
+    }
+}
+
+impl FromStr for FieldPath {
+    type Err = InputError;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let tokens: Vec<_> = match input.strip_prefix('/') {
+            Some(pointer) => pointer.split('/').map(|token| token.replace("~1", "/").replace("~0", "~")).collect(),
+            None => input.split('.').map(String::from).collect(),
+        };
+        if input.is_empty() || tokens.iter().any(String::is_empty) {
+            return Err(InputError::Malformed(format!("invalid field path '{input}'").into()));
+        }
+        Ok(Self { tokens })
+    }
+}
+
+/// Parse a single JSON line and extract the URL at the given path out of it.
+///
+/// # Example
+///
+/// ```
+/// # use trustrl::input::jsonl::parse_json_line;
+/// # fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// let (record, url) = parse_json_line(r#"{"event": {"url": "http://example.com"}}"#, &"/event/url".parse()?)?;
+/// assert_eq!(url, "http://example.com");
+/// # Ok(())
+/// # }
+/// ```
+pub fn parse_json_line(line: &str, path: &FieldPath) -> Result<(Value, String), InputError> {
+    let record: Value = serde_json::from_str(line).map_err(|e| InputError::Malformed(e.to_string().into()))?;
+    let url = match path.get(&record) {
+        Some(Value::String(url)) => url.clone(),
+        Some(_) => return Err(InputError::Malformed("URL field is not a string".into())),
+        None => return Err(InputError::Malformed("URL field not found".into())),
+    };
+    Ok((record, url))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+    use serde_json::json;
+
+    #[rstest]
+    #[case::pointer("/a/b", &["a", "b"])]
+    #[case::pointer_escapes("/a~1b/c~0d", &["a/b", "c~d"])]
+    #[case::dotted("a.b", &["a", "b"])]
+    #[case::single("url", &["url"])]
+    fn parse_path(#[case] input: &str, #[case] expected: &[&str]) {
+        let path: FieldPath = input.parse().expect("parse failed");
+        assert_eq!(path.tokens, expected);
+    }
+
+    #[rstest]
+    #[case::empty("")]
+    #[case::root("/")]
+    #[case::empty_segment("a..b")]
+    fn parse_path_failure(#[case] input: &str) {
+        assert!(input.parse::<FieldPath>().is_err());
+    }
+
+    #[rstest]
+    #[case::top_level(r#"{"url": "a.com", "x": 1}"#, "url", "a.com")]
+    #[case::nested(r#"{"req": {"url": "a.com"}}"#, "req.url", "a.com")]
+    #[case::array(r#"{"links": ["a.com", "b.com"]}"#, "/links/1", "b.com")]
+    fn parse_lines(#[case] line: &str, #[case] path: &str, #[case] expected: &str) {
+        let (_, url) = parse_json_line(line, &path.parse().unwrap()).expect("parse failed");
+        assert_eq!(url, expected);
+    }
+
+    #[rstest]
+    #[case::not_json("a.com", "url")]
+    #[case::missing(r#"{"link": "a.com"}"#, "url")]
+    #[case::not_string(r#"{"url": 42}"#, "url")]
+    #[case::through_string(r#"{"url": "a.com"}"#, "url.x")]
+    fn parse_line_failure(#[case] line: &str, #[case] path: &str) {
+        let result = parse_json_line(line, &path.parse().unwrap());
+        assert!(result.is_err(), "result was {result:?}");
+    }
+
+    #[rstest]
+    #[case::replace(json!({"url": "a.com"}), "url", json!({"url": "x"}))]
+    #[case::new_field(json!({"url": "a.com"}), "host", json!({"url": "a.com", "host": "x"}))]
+    #[case::new_object(json!({"url": "a.com"}), "out.host", json!({"url": "a.com", "out": {"host": "x"}}))]
+    #[case::array(json!({"links": ["a", "b"]}), "/links/1", json!({"links": ["a", "x"]}))]
+    fn set_fields(#[case] mut record: Value, #[case] path: &str, #[case] expected: Value) {
+        let path: FieldPath = path.parse().unwrap();
+        path.set(&mut record, "x".into()).expect("set failed");
+        assert_eq!(record, expected);
+    }
+
+    #[rstest]
+    #[case::through_string(json!({"url": "a.com"}), "url.host")]
+    #[case::array_out_of_bounds(json!({"links": []}), "links.0")]
+    fn set_field_failure(#[case] mut record: Value, #[case] path: &str) {
+        let path: FieldPath = path.parse().unwrap();
+        assert!(path.set(&mut record, "x".into()).is_err());
+    }
+}
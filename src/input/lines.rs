@@ -0,0 +1,119 @@
+//! Line-based input reading.
+
+use super::InputError;
+use std::io::BufRead;
+
+/// A line read from an input.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Line {
+    /// The 1-based line number.
+    pub number: usize,
+
+    /// The contents of the line, without any surrounding whitespace if lines are terminated by newlines.
+    pub text: String,
+}
+
+/// Reads the lines in an input, the way a hand-edited list of URLs is expected to be read.
+///
+/// This means:
+/// * A byte order mark at the beginning of the input is ignored.
+/// * Lines can be terminated by either `\n` or `\r\n`.
+/// * Whitespace surrounding each line is removed.
+/// * Blank lines and lines starting with `#` are skipped.
+/// * A line that isn't valid UTF-8 produces an error, but reading can continue after it.
+///
+/// Lines are terminated by a newline by default, but any other delimiter, like NUL, can be used instead. Lines
+/// terminated by anything other than a newline are kept as they are, other than skipping empty and comment lines,
+/// since they're typically generated by other tools rather than edited by hand.
+pub struct LineReader<R: BufRead> {
+    reader: R,
+    delimiter: u8,
+    number: usize,
+    buffer: Vec<u8>,
+}
+
+impl<R: BufRead> LineReader<R> {
+    /// Construct a new line reader.
+    pub fn new(reader: R) -> Self {
+        Self::with_delimiter(reader, b'\n')
+    }
+
+    /// Construct a new line reader that splits lines using the given delimiter.
+    pub fn with_delimiter(reader: R, delimiter: u8) -> Self {
+        Self { reader, delimiter, number: 0, buffer: Vec::new() }
+    }
+
+    fn read_line(&mut self) -> Result<Option<String>, InputError> {
+        self.buffer.clear();
+        if self.reader.read_until(self.delimiter, &mut self.buffer)? == 0 {
+            return Ok(None);
+        }
+        self.number += 1;
+        let mut line = self.buffer.strip_suffix(&[self.delimiter]).unwrap_or(&self.buffer);
+        if self.number == 1 {
+            line = line.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(line);
+        }
+        match std::str::from_utf8(line) {
+            Ok(line) if self.delimiter == b'\n' => Ok(Some(line.trim().to_string())),
+            Ok(line) => Ok(Some(line.to_string())),
+            Err(e) => Err(InputError::Line(self.number, InputError::Malformed(e.to_string().into()).into())),
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for LineReader<R> {
+    type Item = Result<Line, InputError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.read_line() {
+                Ok(Some(text)) if text.is_empty() || text.starts_with('#') => continue,
+                Ok(Some(text)) => return Some(Ok(Line { number: self.number, text })),
+                Ok(None) => return None,
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    fn read(input: &[u8]) -> Vec<Result<(usize, String), String>> {
+        LineReader::new(input)
+            .map(|line| line.map(|line| (line.number, line.text)).map_err(|e| e.to_string()))
+            .collect()
+    }
+
+    #[rstest]
+    #[case::plain(b"a.com\nb.com\n", &[(1, "a.com"), (2, "b.com")])]
+    #[case::no_trailing_newline(b"a.com\nb.com", &[(1, "a.com"), (2, "b.com")])]
+    #[case::crlf(b"a.com\r\nb.com\r\n", &[(1, "a.com"), (2, "b.com")])]
+    #[case::bom(b"\xEF\xBB\xBFa.com\n", &[(1, "a.com")])]
+    #[case::whitespace(b"  a.com \t\n", &[(1, "a.com")])]
+    #[case::blank(b"a.com\n\n   \nb.com\n", &[(1, "a.com"), (4, "b.com")])]
+    #[case::comments(b"# urls\na.com\n  # indented\nb.com#fragment\n", &[(2, "a.com"), (4, "b.com#fragment")])]
+    fn read_lines(#[case] input: &[u8], #[case] expected: &[(usize, &str)]) {
+        let expected: Vec<_> = expected.iter().map(|(number, text)| Ok((*number, text.to_string()))).collect();
+        assert_eq!(read(input), expected);
+    }
+
+    #[test]
+    fn nul_delimiter() {
+        let lines: Vec<_> = LineReader::with_delimiter(&b"a.com/x y\nz\0\0 b.com \0"[..], b'\0')
+            .map(|line| line.expect("read failed").text)
+            .collect();
+        assert_eq!(lines, &["a.com/x y\nz", " b.com "]);
+    }
+
+    #[test]
+    fn invalid_utf8() {
+        let lines = read(b"a.com\n\xff\xfe\nb.com\n");
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0], Ok((1, "a.com".into())));
+        assert!(lines[1].as_ref().unwrap_err().starts_with("line 2: "), "line was {:?}", lines[1]);
+        assert_eq!(lines[2], Ok((3, "b.com".into())));
+    }
+}
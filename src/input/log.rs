@@ -0,0 +1,298 @@
+//! Web server access log parsing.
+
+use super::{InputError, InputRecord};
+use crate::render::Fields;
+use serde_json::Value;
+use std::borrow::Cow;
+
+/// A web server access log format.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LogFormat {
+    /// The Common Log Format, `%h %l %u %t "%r" %>s %b`.
+    Common,
+
+    /// The Combined Log Format, which appends the referer and user agent to the common one.
+    Combined,
+
+    /// Apache's `vhost_combined` format, which prefixes the combined one with `%v:%p`.
+    VhostCombined,
+
+    /// nginx's default format.
+    ///
+    /// This is the combined format, optionally followed by the `X-Forwarded-For` header.
+    Nginx,
+}
+
+/// Parses access log lines into URLs.
+///
+/// The URL is built out of the request target in each line. If the target is not an absolute URL, the host
+/// is taken from the virtual host in the line, if the format has one, or from the default host otherwise.
+///
+/// Every other field in the line is made available in the record's fields:
+/// * remote_addr
+/// * remote_user
+/// * time
+/// * method
+/// * protocol
+/// * status
+/// * bytes
+/// * referer (all but the common format)
+/// * user_agent (all but the common format)
+/// * vhost (vhost combined format only)
+/// * forwarded_for (nginx format only)
+pub struct LogParser<'a> {
+    format: LogFormat,
+    default_host: Option<&'a str>,
+}
+
+impl<'a> LogParser<'a> {
+    /// Construct a new parser for the given format.
+    pub fn new(format: LogFormat, default_host: Option<&'a str>) -> Self {
+        Self { format, default_host }
+    }
+
+    /// Parse a single log line.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use trustrl::input::log::{LogFormat, LogParser};
+    /// # fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let parser = LogParser::new(LogFormat::Common, Some("example.com"));
+    /// let record = parser.parse(r#"127.0.0.1 - - [10/Oct/2000:13:55:36 -0700] "GET /a.gif HTTP/1.0" 200 2326"#)?;
+    /// assert_eq!(record.url, "http://example.com/a.gif");
+    /// assert_eq!(record.fields["status"], 200);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn parse(&self, line: &str) -> Result<InputRecord, InputError> {
+        use LogFormat::*;
+        let mut tokens = Tokens::new(line);
+        let mut fields = Fields::new();
+        let vhost = match self.format {
+            VhostCombined => {
+                let vhost = tokens.expect("virtual host")?;
+                fields.insert("vhost".into(), text(&vhost));
+                Some(vhost)
+            }
+            _ => None,
+        };
+        fields.insert("remote_addr".into(), text(&tokens.expect("remote address")?));
+        tokens.expect("identity")?;
+        fields.insert("remote_user".into(), text(&tokens.expect("remote user")?));
+        fields.insert("time".into(), text(&tokens.expect("time")?));
+        let request = tokens.expect("request line")?;
+        fields.insert("status".into(), number(&tokens.expect("status")?, "status")?);
+        fields.insert("bytes".into(), number(&tokens.expect("bytes")?, "bytes")?);
+        if self.format != Common {
+            fields.insert("referer".into(), text(&tokens.expect("referer")?));
+            fields.insert("user_agent".into(), text(&tokens.expect("user agent")?));
+        }
+        if self.format == Nginx {
+            let forwarded_for = tokens.next_token()?.map(|value| text(&value)).unwrap_or(Value::Null);
+            fields.insert("forwarded_for".into(), forwarded_for);
+        }
+
+        let mut parts = request.split(' ').filter(|part| !part.is_empty());
+        let (method, target) = match (parts.next(), parts.next()) {
+            (Some(method), Some(target)) => (method, target),
+            _ => return Err(InputError::Malformed(format!("malformed request line '{request}'").into())),
+        };
+        fields.insert("method".into(), method.into());
+        fields.insert("protocol".into(), parts.next().map(Value::from).unwrap_or(Value::Null));
+        let url = self.build_url(target, vhost.as_deref())?;
+        Ok(InputRecord { url, fields })
+    }
+
+    fn build_url(&self, target: &str, vhost: Option<&str>) -> Result<String, InputError> {
+        if target.contains("://") {
+            return Ok(target.to_string());
+        }
+        if !target.starts_with('/') {
+            return Err(InputError::Malformed(format!("unsupported request target '{target}'").into()));
+        }
+        let vhost = vhost.filter(|vhost| *vhost != "-").map(Self::split_vhost);
+        let (host, port) = match (vhost, self.default_host) {
+            (Some((host, port)), _) => (host, port),
+            (None, Some(host)) => (host, None),
+            (None, None) => return Err(InputError::Malformed("no host available to build URL".into())),
+        };
+        let url = match port {
+            Some(443) => format!("https://{host}{target}"),
+            Some(80) | None => format!("http://{host}{target}"),
+            Some(port) => format!("http://{host}:{port}{target}"),
+        };
+        Ok(url)
+    }
+
+    fn split_vhost(vhost: &str) -> (&str, Option<u16>) {
+        match vhost.rsplit_once(':') {
+            Some((host, port)) => match port.parse() {
+                Ok(port) => (host, Some(port)),
+                Err(_) => (vhost, None),
+            },
+            None => (vhost, None),
+        }
+    }
+}
+
+// A missing value is logged as a dash.
+fn text(value: &str) -> Value {
+    match value {
+        "-" => Value::Null,
+        _ => value.into(),
+    }
+}
+
+fn number(value: &str, name: &'static str) -> Result<Value, InputError> {
+    match value {
+        "-" => Ok(Value::Null),
+        _ => match value.parse::<u64>() {
+            Ok(value) => Ok(value.into()),
+            Err(_) => Err(InputError::Malformed(format!("invalid {name} '{value}'").into())),
+        },
+    }
+}
+
+struct Tokens<'a> {
+    rest: &'a str,
+}
+
+impl<'a> Tokens<'a> {
+    fn new(line: &'a str) -> Self {
+        Self { rest: line }
+    }
+
+    fn expect(&mut self, name: &'static str) -> Result<Cow<'a, str>, InputError> {
+        self.next_token()?.ok_or_else(|| InputError::Malformed(format!("missing {name}").into()))
+    }
+
+    fn next_token(&mut self) -> Result<Option<Cow<'a, str>>, InputError> {
+        self.rest = self.rest.trim_start();
+        let token = match self.rest.as_bytes().first() {
+            None => return Ok(None),
+            Some(b'"') => self.quoted()?,
+            Some(b'[') => {
+                let end = self.rest.find(']').ok_or(InputError::Malformed("unterminated '['".into()))?;
+                let token = &self.rest[1..end];
+                self.rest = &self.rest[end + 1..];
+                Cow::Borrowed(token)
+            }
+            Some(_) => {
+                let end = self.rest.find(' ').unwrap_or(self.rest.len());
+                let token = &self.rest[..end];
+                self.rest = &self.rest[end..];
+                Cow::Borrowed(token)
+            }
+        };
+        Ok(Some(token))
+    }
+
+    // Apache escapes quotes and backslashes using a backslash while nginx uses `\xHH`.
+    fn quoted(&mut self) -> Result<Cow<'a, str>, InputError> {
+        let body = &self.rest[1..];
+        match body.find(['"', '\\']) {
+            Some(index) if body.as_bytes()[index] == b'"' => {
+                self.rest = &body[index + 1..];
+                return Ok(Cow::Borrowed(&body[..index]));
+            }
+            Some(_) => (),
+            None => return Err(InputError::Malformed("unterminated '\"'".into())),
+        };
+        let bytes = body.as_bytes();
+        let mut output = Vec::new();
+        let mut index = 0;
+        while let Some(&byte) = bytes.get(index) {
+            match (byte, bytes.get(index + 1)) {
+                (b'"', _) => {
+                    self.rest = &body[index + 1..];
+                    return Ok(Cow::Owned(String::from_utf8_lossy(&output).into_owned()));
+                }
+                (b'\\', Some(b'x')) => match body.get(index + 2..index + 4).map(|hex| u8::from_str_radix(hex, 16)) {
+                    Some(Ok(byte)) => {
+                        output.push(byte);
+                        index += 4;
+                    }
+                    _ => {
+                        output.push(b'\\');
+                        index += 1;
+                    }
+                },
+                (b'\\', Some(&byte)) => {
+                    output.push(byte);
+                    index += 2;
+                }
+                (byte, _) => {
+                    output.push(byte);
+                    index += 1;
+                }
+            }
+        }
+        Err(InputError::Malformed("unterminated '\"'".into()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    const COMMON: &str = r#"127.0.0.1 - frank [10/Oct/2000:13:55:36 -0700] "GET /apache_pb.gif?a=b HTTP/1.0" 200 2326"#;
+    const COMBINED: &str = r#"127.0.0.1 - - [10/Oct/2000:13:55:36 -0700] "POST /login HTTP/1.1" 302 - "http://example.com/start" "Mozilla/4.08 [en] (Win98; I ;Nav)""#;
+
+    #[rstest]
+    #[case::common(LogFormat::Common, COMMON, "http://example.com/apache_pb.gif?a=b")]
+    #[case::combined(LogFormat::Combined, COMBINED, "http://example.com/login")]
+    #[case::nginx(LogFormat::Nginx, COMBINED, "http://example.com/login")]
+    #[case::nginx_forwarded_for(LogFormat::Nginx, &format!("{COMBINED} \"10.0.0.1\""), "http://example.com/login")]
+    #[case::vhost(LogFormat::VhostCombined, &format!("foo.com:80 {COMBINED}"), "http://foo.com/login")]
+    #[case::vhost_https(LogFormat::VhostCombined, &format!("foo.com:443 {COMBINED}"), "https://foo.com/login")]
+    #[case::vhost_port(LogFormat::VhostCombined, &format!("foo.com:8080 {COMBINED}"), "http://foo.com:8080/login")]
+    #[case::absolute_target(
+        LogFormat::Common,
+        r#"1.2.3.4 - - [10/Oct/2000:13:55:36 -0700] "GET http://foo.com/x HTTP/1.1" 200 1"#,
+        "http://foo.com/x"
+    )]
+    fn parse_urls(#[case] format: LogFormat, #[case] line: &str, #[case] expected_url: &str) {
+        let record = LogParser::new(format, Some("example.com")).parse(line).expect("parse failed");
+        assert_eq!(record.url, expected_url);
+    }
+
+    #[test]
+    fn parse_fields() {
+        let line = format!("{COMBINED} \"10.0.0.1\"");
+        let record = LogParser::new(LogFormat::Nginx, Some("example.com")).parse(&line).expect("parse failed");
+        let fields = record.fields;
+        assert_eq!(fields["remote_addr"], "127.0.0.1");
+        assert_eq!(fields["remote_user"], Value::Null);
+        assert_eq!(fields["time"], "10/Oct/2000:13:55:36 -0700");
+        assert_eq!(fields["method"], "POST");
+        assert_eq!(fields["protocol"], "HTTP/1.1");
+        assert_eq!(fields["status"], 302);
+        assert_eq!(fields["bytes"], Value::Null);
+        assert_eq!(fields["referer"], "http://example.com/start");
+        assert_eq!(fields["user_agent"], "Mozilla/4.08 [en] (Win98; I ;Nav)");
+        assert_eq!(fields["forwarded_for"], "10.0.0.1");
+    }
+
+    #[rstest]
+    #[case::apache(r#"a \"quoted\" \\ agent"#, r#"a "quoted" \ agent"#)]
+    #[case::nginx(r#"a \x22quoted\x22 agent"#, r#"a "quoted" agent"#)]
+    fn escaped_quotes(#[case] agent: &str, #[case] expected: &str) {
+        let line = format!(r#"1.2.3.4 - - [t] "GET / HTTP/1.1" 200 1 "-" "{agent}""#);
+        let record = LogParser::new(LogFormat::Combined, Some("example.com")).parse(&line).expect("parse failed");
+        assert_eq!(record.fields["user_agent"], expected);
+    }
+
+    #[rstest]
+    #[case::no_host(None, COMMON)]
+    #[case::truncated(Some("example.com"), r#"127.0.0.1 - - [10/Oct/2000:13:55:36 -0700] "GET / HTTP/1.0""#)]
+    #[case::unterminated_quote(Some("example.com"), r#"127.0.0.1 - - [t] "GET / HTTP/1.0 200 1"#)]
+    #[case::bad_request(Some("example.com"), r#"127.0.0.1 - - [t] "-" 400 0"#)]
+    #[case::asterisk_target(Some("example.com"), r#"127.0.0.1 - - [t] "OPTIONS * HTTP/1.1" 200 0"#)]
+    #[case::bad_status(Some("example.com"), r#"127.0.0.1 - - [t] "GET / HTTP/1.1" abc 0"#)]
+    fn parse_failure(#[case] default_host: Option<&str>, #[case] line: &str) {
+        let result = LogParser::new(LogFormat::Common, default_host).parse(line);
+        assert!(result.is_err(), "result was {result:?}");
+    }
+}
@@ -0,0 +1,55 @@
+//! Pairs of URLs, like the old and new URLs of a site migration.
+
+use super::InputError;
+
+/// Split a line into a pair of URLs.
+///
+/// The URLs are separated by a tab or, if there isn't one, by whitespace. Any whitespace around them is ignored.
+///
+/// # Example
+///
+/// ```
+/// # use trustrl::input::pairs::parse_pair;
+/// # fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// let (old, new) = parse_pair("http://example.com/old\thttps://example.com/new")?;
+/// assert_eq!(old, "http://example.com/old");
+/// assert_eq!(new, "https://example.com/new");
+/// # Ok(())
+/// # }
+/// ```
+pub fn parse_pair(line: &str) -> Result<(&str, &str), InputError> {
+    let line = line.trim();
+    let pair = match line.split_once('\t') {
+        Some((old, new)) => Some((old.trim_end(), new.trim_start())).filter(|(_, new)| !new.contains('\t')),
+        None => line
+            .split_once(char::is_whitespace)
+            .map(|(old, new)| (old, new.trim_start()))
+            .filter(|(_, new)| !new.contains(char::is_whitespace)),
+    };
+    match pair {
+        Some((old, new)) if !old.is_empty() && !new.is_empty() => Ok((old, new)),
+        _ => Err(InputError::Malformed("expected two URLs separated by a tab or whitespace".into())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case::tab("a.com/x\tb.com/y", ("a.com/x", "b.com/y"))]
+    #[case::spaces("  a.com/x   b.com/y ", ("a.com/x", "b.com/y"))]
+    #[case::spaces_in_urls("a.com/x y\tb.com/y z", ("a.com/x y", "b.com/y z"))]
+    fn pairs(#[case] line: &str, #[case] expected: (&str, &str)) {
+        assert_eq!(parse_pair(line).expect("parse failed"), expected);
+    }
+
+    #[rstest]
+    #[case::single("a.com/x")]
+    #[case::three_columns("a.com\tb.com\tc.com")]
+    #[case::three_words("a.com b.com c.com")]
+    fn invalid_pairs(#[case] line: &str) {
+        assert!(parse_pair(line).is_err());
+    }
+}
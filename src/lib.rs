@@ -3,11 +3,15 @@
 #![deny(missing_docs)]
 #![forbid(unsafe_code)]
 
+pub mod components;
+pub mod explain;
+pub mod input;
 pub mod parse;
 pub mod render;
+pub mod service;
 pub mod transform;
 
 pub use parse::parse_url;
-pub use render::{RenderError, UrlRenderer, UrlTemplate};
-pub use transform::{TransformError, UrlTransformation};
+pub use render::{Fields, RenderError, TemplateKey, UrlRenderer, UrlTemplate};
+pub use transform::{TransformError, Transformation, UrlTransformation};
 pub use url::Url;
@@ -1,28 +1,186 @@
-use clap::{error::ErrorKind, Args, CommandFactory, Parser};
+use clap::{error::ErrorKind, ArgMatches, Args, CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum};
 use regex::Regex;
+use serde_json::json;
 use std::{
+    collections::BTreeMap,
+    env,
+    fmt,
     fs::File,
-    io::{self, stdin, BufRead, BufReader, Write},
+    io::{self, stdin, BufRead, BufReader, IsTerminal, Read, Write},
     iter,
+    path::PathBuf,
     process::exit,
+    sync::{mpsc, Mutex},
+    thread,
+};
+use trustrl::{
+    explain::Explanation,
+    input::{
+        delimited::{Column, DelimitedReader},
+        follow::FollowReader,
+        har::parse_har,
+        jsonl::{parse_json_line, FieldPath},
+        lines::{Line, LineReader},
+        log::{LogFormat, LogParser},
+        pairs::parse_pair,
+        InputError, InputRecord,
+    },
+    parse::UrlParseError,
+    service::{process_urls, rpc, ProcessRequest},
+    parse_url,
+    render::{
+        delimited::{DelimitedRenderer, DelimitedWriter},
+        diff::DiffRenderer,
+        escape::Escape,
+        http::{HttpClient, HttpClientRenderer},
+        mapping::{MappingFormat, MappingRenderer},
+        redirect::{RedirectFormat, RedirectRenderer},
+        sitemap::{SitemapRenderer, SitemapWriter, MAX_SITEMAP_URLS},
+        table::{BlockRenderer, TableRenderer},
+        ListFraming, Origin, Render, RenderContext,
+    },
+    transform, Fields, RenderError, TransformError, UrlRenderer, UrlTemplate, UrlTransformation,
 };
-use trustrl::{parse_url, TransformError, UrlRenderer, UrlTransformation};
 use url::Url;
 
 #[derive(Parser)]
-#[clap(version, about)]
+#[clap(version, about, subcommand_negates_reqs = true)]
 struct Cli {
+    #[clap(subcommand)]
+    command: Option<Command>,
+
     #[clap(flatten)]
     input: Input,
 
+    /// The format of the file in `--urls-path`.
+    #[clap(long, value_enum, default_value_t = InputFormat::List)]
+    input_format: InputFormat,
+
+    /// The host to use when an access log line doesn't contain one.
+    #[clap(long)]
+    log_host: Option<String>,
+
+    /// The column that contains the URL in CSV/TSV files, either by name or by 1-based number. Defaults to the
+    /// first column.
+    #[clap(long)]
+    column: Option<Column>,
+
+    /// Write the result of CSV/TSV rows into a new column with this name rather than replacing the URL.
+    #[clap(long)]
+    output_column: Option<String>,
+
+    /// The CSV/TSV file doesn't have a header row.
+    #[clap(long)]
+    no_header: bool,
+
+    /// The field that contains the URL in JSON Lines records, either as a JSON pointer or a dotted path.
+    #[clap(long, default_value = "url")]
+    field: FieldPath,
+
+    /// Write the result of JSON Lines records into this field rather than replacing the URL.
+    #[clap(long)]
+    output_field: Option<FieldPath>,
+
+    /// Keep reading the file in `--urls-path` as it grows, like `tail -F`, writing out every result straight away.
+    /// Only the lines appended to it are processed.
+    #[clap(long, requires = "urls_path", conflicts_with = "jobs")]
+    follow: bool,
+
+    /// Process the lines already in the file when using `--follow`, rather than only the ones appended to it.
+    #[clap(long, requires = "follow")]
+    from_start: bool,
+
+    /// Split input records and terminate output records using NUL rather than newlines.
+    #[clap(short = '0', long)]
+    null: bool,
+
+    /// The number of threads used to process line based inputs in `--urls-path`, or 0 to use one per CPU.
+    #[clap(long, default_value_t = 1)]
+    jobs: usize,
+
+    /// Write results as soon as they're ready when using `--jobs`, rather than in input order.
+    #[clap(long, requires = "jobs")]
+    unordered: bool,
+
     /// The template to be used to render the URL.
     #[clap(short = 't', long, default_value = "{url}", group = "outputs")]
     template: String,
 
+    /// Escape the values of the keys in the template for the context the output is used in. Keys can also be
+    /// escaped individually, e.g. `{path|shell}`.
+    #[clap(
+        long,
+        value_enum,
+        value_name = "ESCAPE",
+        conflicts_with_all = [
+            "output_json", "csv", "tsv", "table", "blocks", "redirects", "sitemap", "http_client", "diff"
+        ]
+    )]
+    escape: Option<Escaping>,
+
     /// Output URLs in JSON format.
     #[clap(short = 'j', long = "to-json", group = "outputs")]
     output_json: bool,
 
+    /// Output URLs as CSV rows with these comma separated template keys as columns, e.g. `host,path,query:id`.
+    #[clap(long, value_delimiter = ',', value_name = "COLUMNS", group = "outputs", group = "delimited")]
+    csv: Option<Vec<String>>,
+
+    /// Output URLs as TSV rows with these comma separated template keys as columns.
+    #[clap(long, value_delimiter = ',', value_name = "COLUMNS", group = "outputs", group = "delimited")]
+    tsv: Option<Vec<String>>,
+
+    /// Write a header row with the column names first when using `--csv` or `--tsv`.
+    #[clap(long, requires = "delimited")]
+    header: bool,
+
+    /// Output URLs as an aligned table with these comma separated template keys as columns, fitting the terminal.
+    #[clap(long, value_delimiter = ',', value_name = "COLUMNS", group = "outputs")]
+    table: Option<Vec<String>>,
+
+    /// Output URLs as blocks with a labeled line for each of these comma separated template keys.
+    #[clap(long, value_delimiter = ',', value_name = "COLUMNS", group = "outputs")]
+    blocks: Option<Vec<String>>,
+
+    /// Output redirects from every URL to the one it's transformed into, as web server configuration.
+    #[clap(long, value_enum, value_name = "FORMAT", group = "outputs")]
+    redirects: Option<Redirects>,
+
+    /// Output URLs as a sitemap for the site at this URL, e.g. `https://example.com/`. URLs with another scheme,
+    /// host or port, or outside its directory, are rejected.
+    #[clap(long, value_name = "URL", group = "outputs")]
+    sitemap: Option<Url>,
+
+    /// Write sitemaps into this directory rather than stdout, starting a new one every 50,000 URLs and listing them
+    /// in a `sitemap.xml` index. Sitemaps are expected to be served from the directory of `--sitemap`.
+    #[clap(long, value_name = "DIR", requires = "sitemap", conflicts_with = "follow")]
+    sitemap_dir: Option<PathBuf>,
+
+    /// Output a command or raw request that fetches every URL, moving credentials into basic authentication.
+    #[clap(long, value_enum, value_name = "CLIENT", group = "outputs")]
+    http_client: Option<Client>,
+
+    /// Output every URL next to the one it was transformed from, along with the components that changed.
+    #[clap(long, group = "outputs")]
+    diff: bool,
+
+    /// Only output the URLs that were changed by the transformations.
+    #[clap(long)]
+    changed_only: bool,
+
+    /// Output the input every URL was read from next to its result, either as a TSV row or as a JSON object.
+    #[clap(
+        long,
+        value_enum,
+        value_name = "FORMAT",
+        conflicts_with_all = ["csv", "tsv", "table", "blocks", "redirects", "sitemap", "diff"]
+    )]
+    mapping: Option<Mapping>,
+
+    /// Highlight URL components in the template output using colors, marking the ones that were changed.
+    #[clap(long, value_enum, default_value_t = ColorChoice::Auto)]
+    color: ColorChoice,
+
     /// Set the URL's scheme.
     #[clap(short = 's', long)]
     scheme: Option<String>,
@@ -80,26 +238,379 @@ struct Cli {
     sort_query_string: bool,
 }
 
+#[derive(Subcommand)]
+enum Command {
+    /// Serve requests to process URLs over HTTP, using the transformations and template in the options as
+    /// the default pipeline.
+    Serve(ServeArgs),
+
+    /// Run as a co-process that handles JSON-RPC requests read from stdin, one per line, and writes every
+    /// response to stdout.
+    Rpc,
+
+    /// Break URLs down into their components, showing where every component is and which values were
+    /// defaulted.
+    Explain(ExplainArgs),
+}
+
 #[derive(Args)]
-#[group(required = true, multiple = false)]
+struct ServeArgs {
+    /// The address to listen on.
+    #[clap(long, default_value = "127.0.0.1:8080")]
+    listen: String,
+}
+
+#[derive(Args)]
+struct ExplainArgs {
+    /// The URLs to explain. If none are given, they're read from stdin, one per line.
+    urls: Vec<String>,
+}
+
+#[derive(Args)]
+#[group(required = true, multiple = true)]
 struct Input {
-    /// The URL to be used.
-    url: Option<String>,
+    /// The URLs to be used.
+    urls: Vec<String>,
 
-    /// A path to a list of URLs to process.
+    /// A path to a list of URLs to process. Can be used multiple times, and `-` reads from stdin.
     #[clap(long)]
-    urls_path: Option<String>,
+    urls_path: Vec<String>,
+}
+
+impl Input {
+    // Whether more than a single URL can be processed.
+    fn is_multi_url(&self) -> bool {
+        self.urls.len() > 1 || !self.urls_path.is_empty()
+    }
+
+    // The URLs and files to process, in the order they were given in.
+    fn sources(&self, matches: &ArgMatches) -> Vec<InputSource<'_>> {
+        let urls =
+            matches.indices_of("urls").into_iter().flatten().zip(self.urls.iter().map(|url| InputSource::Url(url)));
+        let paths = matches
+            .indices_of("urls_path")
+            .into_iter()
+            .flatten()
+            .zip(self.urls_path.iter().map(|path| InputSource::Path(path)));
+        let mut sources: Vec<_> = urls.chain(paths).collect();
+        sources.sort_by_key(|(index, _)| *index);
+        sources.into_iter().map(|(_, source)| source).collect()
+    }
+}
+
+// A URL or a file given in the command line.
+enum InputSource<'a> {
+    Url(&'a str),
+    Path(&'a str),
 }
 
 #[cfg(test)]
 mod test {
-    use super::Cli;
+    use super::{Cli, InputParser, InputSource, Pipeline, Processor, BATCH_SIZE};
+    use clap::{CommandFactory, FromArgMatches, Parser};
+    use std::{
+        io::Write,
+        sync::atomic::{AtomicUsize, Ordering::SeqCst},
+        thread,
+        time::Duration,
+    };
+    use trustrl::{
+        render::{Render, RenderContext},
+        Fields, RenderError,
+    };
+    use url::Url;
 
     #[test]
     fn verify_cli() {
-        use clap::CommandFactory;
         Cli::command().debug_assert();
     }
+
+    #[test]
+    fn input_order() {
+        let matches = Cli::command().get_matches_from(["trustrl", "a.com", "--urls-path", "x.txt", "b.com"]);
+        let cli = Cli::from_arg_matches(&matches).unwrap();
+        let sources: Vec<_> = cli
+            .input
+            .sources(&matches)
+            .into_iter()
+            .map(|source| match source {
+                InputSource::Url(url) => format!("url {url}"),
+                InputSource::Path(path) => format!("path {path}"),
+            })
+            .collect();
+        assert_eq!(sources, ["url a.com", "path x.txt", "url b.com"]);
+    }
+
+    #[test]
+    fn urls_named_like_commands() {
+        let cli = Cli::try_parse_from(["trustrl", "--", "explain", "serve"]).unwrap();
+        assert!(cli.command.is_none());
+        assert_eq!(cli.input.urls, ["explain", "serve"]);
+
+        let cli = Cli::try_parse_from(["trustrl", "explain", "serve"]).unwrap();
+        assert!(cli.command.is_some());
+    }
+
+    #[test]
+    fn header_requires_delimited_output() {
+        assert!(Cli::try_parse_from(["trustrl", "--header", "a.com"]).is_err());
+        assert!(Cli::try_parse_from(["trustrl", "--header", "--tsv", "host", "a.com"]).is_ok());
+    }
+
+    #[test]
+    fn slow_batch_in_parallel() {
+        // Counts the URLs rendered while the one in `slow.com` is being rendered.
+        #[derive(Default)]
+        struct SlowRenderer {
+            rendered: AtomicUsize,
+            rendered_meanwhile: AtomicUsize,
+        }
+
+        impl Render for SlowRenderer {
+            fn render_with_fields(&self, url: &Url, _: &Fields, _: &mut dyn Write) -> Result<(), RenderError> {
+                if url.host_str() == Some("slow.com") {
+                    let before = self.rendered.load(SeqCst);
+                    thread::sleep(Duration::from_millis(500));
+                    self.rendered_meanwhile.store(self.rendered.load(SeqCst) - before, SeqCst);
+                }
+                self.rendered.fetch_add(1, SeqCst);
+                Ok(())
+            }
+        }
+
+        let renderer = SlowRenderer::default();
+        let parser = InputParser::List;
+        let pipeline = Pipeline::new(&[], &renderer, &parser);
+        let input = format!("slow.com\n{}", "a.com\n".repeat(BATCH_SIZE * 100));
+        let mut processor = Processor::new(RenderContext::new(Vec::new(), b'\n'), pipeline, b'\n').with_jobs(2, true);
+        processor.process_urls(input.as_bytes());
+
+        assert_eq!(renderer.rendered.load(SeqCst), BATCH_SIZE * 100 + 1);
+        // Only the batches waiting for the slow one and the ones queued up for the workers are processed meanwhile,
+        // rather than the whole input.
+        assert!(renderer.rendered_meanwhile.load(SeqCst) <= BATCH_SIZE * 12);
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+enum InputFormat {
+    /// One URL per line.
+    List,
+
+    /// Access logs in Common Log Format.
+    CommonLog,
+
+    /// Access logs in Combined Log Format.
+    CombinedLog,
+
+    /// Access logs in Apache's vhost_combined format.
+    VhostCombinedLog,
+
+    /// Access logs in nginx's default format.
+    NginxLog,
+
+    /// An HTTP Archive (HAR) file.
+    Har,
+
+    /// A CSV file.
+    Csv,
+
+    /// A TSV file.
+    Tsv,
+
+    /// A JSON Lines (NDJSON) file.
+    Jsonl,
+
+    /// Pairs of old and new URLs, one per line, separated by a tab or whitespace. Transformations are applied to
+    /// the new URLs.
+    Pairs,
+}
+
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+enum Client {
+    /// A `curl` command.
+    Curl,
+
+    /// A `wget` command.
+    Wget,
+
+    /// A raw HTTP/1.1 `GET` request.
+    Request,
+}
+
+impl From<Client> for HttpClient {
+    fn from(client: Client) -> Self {
+        match client {
+            Client::Curl => HttpClient::Curl,
+            Client::Wget => HttpClient::Wget,
+            Client::Request => HttpClient::Request,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+enum Redirects {
+    /// Entries in an nginx `map` from the request URI to the new URL.
+    NginxMap,
+
+    /// nginx exact match `location` blocks that return a 301.
+    NginxLocation,
+
+    /// Apache `Redirect 301` lines.
+    ApacheRedirect,
+
+    /// Apache `RewriteRule` lines, matching query strings as well.
+    ApacheRewrite,
+
+    /// A JSON object from old to new URLs.
+    Json,
+}
+
+impl From<Redirects> for RedirectFormat {
+    fn from(redirects: Redirects) -> Self {
+        match redirects {
+            Redirects::NginxMap => RedirectFormat::NginxMap,
+            Redirects::NginxLocation => RedirectFormat::NginxLocation,
+            Redirects::ApacheRedirect => RedirectFormat::ApacheRedirect,
+            Redirects::ApacheRewrite => RedirectFormat::ApacheRewrite,
+            Redirects::Json => RedirectFormat::Json,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+enum Mapping {
+    /// The input and the result separated by a tab.
+    Tsv,
+
+    /// A JSON object with `input` and `output` keys.
+    Json,
+}
+
+impl From<Mapping> for MappingFormat {
+    fn from(mapping: Mapping) -> Self {
+        match mapping {
+            Mapping::Tsv => MappingFormat::Tsv,
+            Mapping::Json => MappingFormat::Json,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+enum Escaping {
+    /// Single quote values for POSIX shells.
+    Shell,
+
+    /// Write values as JSON strings.
+    Json,
+
+    /// Escape values for HTML attributes and text.
+    Html,
+
+    /// Escape values for XML attributes and text.
+    Xml,
+}
+
+impl From<Escaping> for Escape {
+    fn from(escaping: Escaping) -> Self {
+        match escaping {
+            Escaping::Shell => Escape::Shell,
+            Escaping::Json => Escape::Json,
+            Escaping::Html => Escape::Html,
+            Escaping::Xml => Escape::Xml,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+enum ColorChoice {
+    /// Use colors if stdout is a terminal and `NO_COLOR` isn't set.
+    Auto,
+
+    /// Always use colors.
+    Always,
+
+    /// Never use colors.
+    Never,
+}
+
+impl ColorChoice {
+    fn enabled(&self) -> bool {
+        match self {
+            Self::Auto => io::stdout().is_terminal() && env::var_os("NO_COLOR").is_none(),
+            Self::Always => true,
+            Self::Never => false,
+        }
+    }
+}
+
+impl InputFormat {
+    fn parser<'a>(&self, cli: &'a Cli) -> InputParser<'a> {
+        use InputFormat::*;
+        let format = match self {
+            List => return InputParser::List,
+            Har => return InputParser::Har,
+            Csv => return InputParser::Delimited(DelimitedOptions::new(b',', cli)),
+            Tsv => return InputParser::Delimited(DelimitedOptions::new(b'\t', cli)),
+            Jsonl => return InputParser::JsonLines(JsonLinesOptions::new(cli)),
+            Pairs => return InputParser::Pairs,
+            CommonLog => LogFormat::Common,
+            CombinedLog => LogFormat::Combined,
+            VhostCombinedLog => LogFormat::VhostCombined,
+            NginxLog => LogFormat::Nginx,
+        };
+        InputParser::Log(LogParser::new(format, cli.log_host.as_deref()))
+    }
+}
+
+enum InputParser<'a> {
+    List,
+    Log(LogParser<'a>),
+    Har,
+    Delimited(DelimitedOptions<'a>),
+    JsonLines(JsonLinesOptions<'a>),
+    Pairs,
+}
+
+impl<'a> InputParser<'a> {
+    // Whether every input record is written back along with the result, rather than just the result.
+    fn is_passthrough(&self) -> bool {
+        matches!(self, Self::Delimited(_) | Self::JsonLines(_))
+    }
+}
+
+// The column URLs are read from when `--column` isn't used.
+static FIRST_COLUMN: Column = Column::Index(0);
+
+#[derive(Clone, Copy)]
+struct DelimitedOptions<'a> {
+    delimiter: u8,
+    column: &'a Column,
+    output_column: Option<&'a str>,
+    has_headers: bool,
+}
+
+impl<'a> DelimitedOptions<'a> {
+    fn new(delimiter: u8, cli: &'a Cli) -> Self {
+        Self {
+            delimiter,
+            column: cli.column.as_ref().unwrap_or(&FIRST_COLUMN),
+            output_column: cli.output_column.as_deref(),
+            has_headers: !cli.no_header,
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+struct JsonLinesOptions<'a> {
+    field: &'a FieldPath,
+    output_field: Option<&'a FieldPath>,
+}
+
+impl<'a> JsonLinesOptions<'a> {
+    fn new(cli: &'a Cli) -> Self {
+        Self { field: &cli.field, output_field: cli.output_field.as_ref() }
+    }
 }
 
 fn optional_string(value: &str) -> Option<&str> {
@@ -118,7 +629,7 @@ fn optional_vec<T>(value: Vec<T>) -> Option<Vec<T>> {
     }
 }
 
-fn parse_append_query_strings(inputs: &[String]) -> Vec<UrlTransformation> {
+fn parse_append_query_strings(inputs: &[String]) -> Vec<UrlTransformation<'_>> {
     let mut transformations = Vec::new();
     for input in inputs {
         let (key, value) = match input.split_once('=') {
@@ -130,157 +641,775 @@ fn parse_append_query_strings(inputs: &[String]) -> Vec<UrlTransformation> {
     transformations
 }
 
-fn build_transformations(cli: &Cli) -> Vec<UrlTransformation> {
+// Builds the renderer for the chosen output. Plain lists of URLs carry no fields, so for those every key the
+// output uses is checked upfront.
+fn build_renderer<'a>(cli: &'a Cli, parser: &InputParser, record_delimiter: u8) -> Box<dyn Render + Sync + 'a> {
+    let check = |result: Result<(), RenderError>| {
+        if let (Err(e), InputParser::List | InputParser::Pairs) = (result, parser) {
+            Cli::command().error(ErrorKind::InvalidValue, e).exit();
+        }
+    };
+    let delimited = match (&cli.csv, &cli.tsv) {
+        (Some(columns), _) => Some((b',', columns)),
+        (_, Some(columns)) => Some((b'\t', columns)),
+        _ => None,
+    };
+    if cli.output_json {
+        Box::new(UrlRenderer::json())
+    } else if cli.diff {
+        Box::new(DiffRenderer)
+    } else if let Some(base) = &cli.sitemap {
+        Box::new(SitemapRenderer::new(base.clone()))
+    } else if let Some(client) = cli.http_client {
+        Box::new(HttpClientRenderer::new(client.into()))
+    } else if let Some(redirects) = cli.redirects {
+        Box::new(RedirectRenderer::new(redirects.into()))
+    } else if let Some((delimiter, columns)) = delimited {
+        let renderer = DelimitedRenderer::new(delimiter, columns.iter().map(String::as_str))
+            .with_terminator(record_delimiter)
+            .with_header(cli.header);
+        check(renderer.validate::<&str>(&[]));
+        Box::new(renderer)
+    } else if let Some(columns) = &cli.table {
+        let renderer = TableRenderer::new(columns.iter().map(String::as_str), terminal_width());
+        check(renderer.validate::<&str>(&[]));
+        Box::new(renderer)
+    } else if let Some(components) = &cli.blocks {
+        let renderer = BlockRenderer::new(components.iter().map(String::as_str));
+        check(renderer.validate::<&str>(&[]));
+        Box::new(renderer)
+    } else {
+        let highlight = highlighting(cli, parser);
+        let escape = cli.escape.map(Escape::from).unwrap_or_default();
+        let template = UrlTemplate::new(&cli.template).with_highlighting(highlight).with_escape(escape);
+        check(template.validate::<&str>(&[]));
+        Box::new(UrlRenderer::Template(template))
+    }
+}
+
+// Whether templates highlight URL components. Escape codes would end up in the middle of passed through records,
+// JSON mappings and served responses.
+fn highlighting(cli: &Cli, parser: &InputParser) -> bool {
+    cli.command.is_none() && !parser.is_passthrough() && cli.mapping != Some(Mapping::Json) && cli.color.enabled()
+}
+
+// The width of the terminal stdout is attached to. Otherwise, `COLUMNS` is used if it's set, like shells do.
+fn terminal_width() -> usize {
+    const DEFAULT_WIDTH: usize = 80;
+    #[cfg(unix)]
+    if let Ok(size) = rustix::termios::tcgetwinsize(io::stdout()) {
+        if size.ws_col > 0 {
+            return size.ws_col.into();
+        }
+    }
+    env::var("COLUMNS").ok().and_then(|columns| columns.parse().ok()).unwrap_or(DEFAULT_WIDTH)
+}
+
+fn build_transformations(cli: &Cli) -> Vec<UrlTransformation<'_>> {
     iter::empty()
-        .chain(cli.scheme.as_deref().map(UrlTransformation::SetScheme).into_iter())
-        .chain(cli.host.as_deref().map(UrlTransformation::SetHost).into_iter())
-        .chain(cli.port.map(UrlTransformation::SetPort).into_iter())
-        .chain(cli.path.as_deref().map(UrlTransformation::SetPath).into_iter())
-        .chain(cli.user.as_deref().map(UrlTransformation::SetUser).into_iter())
-        .chain(cli.password.as_deref().map(optional_string).map(UrlTransformation::SetPassword).into_iter())
-        .chain(cli.fragment.as_deref().map(optional_string).map(UrlTransformation::SetFragment).into_iter())
-        .chain(cli.redirect.as_deref().map(UrlTransformation::Redirect).into_iter())
-        .chain(cli.append_path.as_deref().map(UrlTransformation::AppendPath).into_iter())
-        .chain(cli.clear_query_string.then_some(UrlTransformation::ClearQueryString).into_iter())
-        .chain(optional_vec(cli.allow_query_string.clone()).map(UrlTransformation::AllowQueryString).into_iter())
-        .chain(optional_vec(cli.deny_query_string.clone()).map(UrlTransformation::DenyQueryString).into_iter())
+        .chain(cli.scheme.as_deref().map(UrlTransformation::SetScheme))
+        .chain(cli.host.as_deref().map(UrlTransformation::SetHost))
+        .chain(cli.port.map(UrlTransformation::SetPort))
+        .chain(cli.path.as_deref().map(UrlTransformation::SetPath))
+        .chain(cli.user.as_deref().map(UrlTransformation::SetUser))
+        .chain(cli.password.as_deref().map(optional_string).map(UrlTransformation::SetPassword))
+        .chain(cli.fragment.as_deref().map(optional_string).map(UrlTransformation::SetFragment))
+        .chain(cli.redirect.as_deref().map(UrlTransformation::Redirect))
+        .chain(cli.append_path.as_deref().map(UrlTransformation::AppendPath))
+        .chain(cli.clear_query_string.then_some(UrlTransformation::ClearQueryString))
+        .chain(optional_vec(cli.allow_query_string.clone()).map(UrlTransformation::AllowQueryString))
+        .chain(optional_vec(cli.deny_query_string.clone()).map(UrlTransformation::DenyQueryString))
         .chain(parse_append_query_strings(&cli.append_query_string))
-        .chain(cli.sort_query_string.then_some(UrlTransformation::SortQueryString).into_iter())
+        .chain(cli.sort_query_string.then_some(UrlTransformation::SortQueryString))
         .collect()
 }
 
-enum RenderMode {
-    Single,
-    JsonList { count: usize },
+macro_rules! exit {
+    ($arg:tt) => {
+        eprintln!($arg);
+        exit(1);
+    };
 }
 
-struct RenderContext<'a, W: Write> {
-    renderer: UrlRenderer<'a>,
-    writer: W,
-    mode: RenderMode,
+// The number of lines handed to a worker thread at a time.
+const BATCH_SIZE: usize = 256;
+
+// Turns input records into rendered output.
+//
+// This only borrows its state so it can be shared by the worker threads used in parallel mode. Errors are
+// reported as they're found and the offending record is skipped.
+#[derive(Clone, Copy)]
+struct Pipeline<'a> {
+    transformations: &'a [UrlTransformation<'a>],
+    renderer: &'a (dyn Render + Sync),
+    parser: &'a InputParser<'a>,
+    changed_only: bool,
+    keep_origin: bool,
 }
 
-impl<'a, W: Write> RenderContext<'a, W> {
-    fn new_single_line(renderer: UrlRenderer<'a>, writer: W) -> Self {
-        Self { renderer, writer, mode: RenderMode::Single }
+impl<'a> Pipeline<'a> {
+    fn new(
+        transformations: &'a [UrlTransformation<'a>],
+        renderer: &'a (dyn Render + Sync),
+        parser: &'a InputParser<'a>,
+    ) -> Self {
+        Self { transformations, renderer, parser, changed_only: false, keep_origin: true }
     }
 
-    fn new_json_list(renderer: UrlRenderer<'a>, writer: W) -> Self {
-        Self { renderer, writer, mode: RenderMode::JsonList { count: 0 } }
+    // Skip the URLs that the transformations leave as they are.
+    fn with_changed_only(mut self, changed_only: bool) -> Self {
+        self.changed_only = changed_only;
+        self
     }
 
-    fn render(&mut self, url: &Url) -> Result<(), Box<dyn std::error::Error>> {
-        use RenderMode::JsonList;
-        match self.mode {
-            JsonList { count: 0 } => write!(self.writer, "[")?,
-            JsonList { .. } => write!(self.writer, ", ")?,
-            _ => (),
+    // Keep the URLs as they were before transforming them, for outputs that show what the transformations
+    // changed. Otherwise they're transformed in place.
+    fn with_origin(mut self, keep_origin: bool) -> Self {
+        self.keep_origin = keep_origin;
+        self
+    }
+
+    fn process_line(&self, line: Line) -> Option<Vec<u8>> {
+        let record = match self.parser {
+            InputParser::Log(parser) => parser.parse(&line.text),
+            InputParser::JsonLines(options) => return self.process_json_line(line, *options),
+            InputParser::Pairs => return self.process_pair(line),
+            _ => Ok(InputRecord::new(line.text)),
         };
-        if matches!(self.mode, RenderMode::JsonList { count: 0 }) {}
-        self.renderer.render(url, &mut self.writer)?;
-        if let RenderMode::JsonList { count } = &mut self.mode {
-            *count += 1;
-        } else {
-            writeln!(self.writer)?;
+        match record {
+            Ok(record) => self.process_record(record, format_args!("line {}", line.number)),
+            Err(e) => {
+                eprintln!("Invalid entry at line {}: {e}", line.number);
+                None
+            }
         }
-        Ok(())
     }
-}
 
-impl<'a, W: Write> Drop for RenderContext<'a, W> {
-    fn drop(&mut self) {
-        if matches!(self.mode, RenderMode::JsonList { .. }) {
-            let _ = writeln!(self.writer, "]");
+    fn process_record(&self, record: InputRecord, location: fmt::Arguments) -> Option<Vec<u8>> {
+        let url = self.parse(&record.url, location)?;
+        self.process_parsed_url(&record.url, url, &record.fields)
+    }
+
+    fn process_parsed_url(&self, input: &str, url: Url, fields: &Fields) -> Option<Vec<u8>> {
+        let origin = self.keep_origin.then(|| url.clone());
+        match self.transform(url) {
+            Ok(transformed) => match &origin {
+                Some(url) => self.render_transformed(Origin { input, url }, &transformed, fields),
+                None => self.render(None, &transformed, fields),
+            },
+            Err(e) => {
+                eprintln!("Error performing transformations: {e}");
+                None
+            }
+        }
+    }
+
+    // The first URL in a pair is the original one and the transformations are applied to the second one.
+    fn process_pair(&self, line: Line) -> Option<Vec<u8>> {
+        let (input, new) = match parse_pair(&line.text) {
+            Ok(pair) => pair,
+            Err(e) => {
+                eprintln!("Invalid entry at line {}: {e}", line.number);
+                return None;
+            }
+        };
+        let url = self.parse(input, format_args!("line {}", line.number))?;
+        let transformed = self.process_to_url(new, format_args!("line {}", line.number))?;
+        self.render_transformed(Origin { input, url: &url }, &transformed, &Fields::new())
+    }
+
+    fn process_json_line(&self, line: Line, options: JsonLinesOptions) -> Option<Vec<u8>> {
+        let (mut record, url) = match parse_json_line(&line.text, options.field) {
+            Ok(output) => output,
+            Err(e) => {
+                eprintln!("Invalid entry at line {}: {e}", line.number);
+                return None;
+            }
+        };
+        let output = self.process_to_url(&url, format_args!("line {}", line.number)).and_then(|url| {
+            self.renderer.render_value(&url, &Fields::new()).map_err(|e| eprintln!("Rendering failed: {e}")).ok()
+        });
+        if let Some(output) = output {
+            if let Err(e) = options.output_field.unwrap_or(options.field).set(&mut record, output) {
+                eprintln!("Failed to write result at line {}: {e}", line.number);
+            }
+        }
+        Some(record.to_string().into_bytes())
+    }
+
+    fn process_to_url(&self, url: &str, location: fmt::Arguments) -> Option<Url> {
+        let url = self.parse(url, location)?;
+        match self.transform(url) {
+            Ok(url) => Some(url),
+            Err(e) => {
+                eprintln!("Error performing transformations: {e}");
+                None
+            }
+        }
+    }
+
+    fn parse(&self, url: &str, location: fmt::Arguments) -> Option<Url> {
+        match parse_url(url) {
+            Ok(url) => Some(url),
+            Err(e) => {
+                eprintln!("Invalid URL '{url}' at {location}: {e}");
+                None
+            }
         }
     }
+
+    fn transform(&self, url: Url) -> Result<Url, TransformError> {
+        transform::apply_all(self.transformations, url)
+    }
+
+    fn render_transformed(&self, origin: Origin, url: &Url, fields: &Fields) -> Option<Vec<u8>> {
+        if self.changed_only && origin.url == url {
+            return None;
+        }
+        self.render(Some(origin), url, fields)
+    }
+
+    // Render a URL, along with the one it was transformed from if there is one.
+    fn render(&self, origin: Option<Origin>, url: &Url, fields: &Fields) -> Option<Vec<u8>> {
+        let mut output = Vec::new();
+        let result = match origin {
+            Some(origin) => self.renderer.render_transformed(&origin, url, fields, &mut output),
+            None => self.renderer.render_with_fields(url, fields, &mut output),
+        };
+        match result {
+            Ok(()) => Some(output),
+            Err(e) => {
+                eprintln!("Rendering failed: {e}");
+                None
+            }
+        }
+    }
+
+    fn render_to_string(&self, url: &Url) -> Option<String> {
+        self.render(None, url, &Fields::new()).map(|output| String::from_utf8_lossy(&output).into_owned())
+    }
 }
 
-macro_rules! exit {
-    ($arg:tt) => {
-        eprintln!($arg);
-        exit(1);
-    };
+// Puts the batches processed by worker threads back in order, unless the order doesn't matter.
+struct Batches {
+    ordered: bool,
+    next: usize,
+    pending: BTreeMap<usize, Vec<Vec<u8>>>,
+}
+
+impl Batches {
+    fn new(ordered: bool) -> Self {
+        Self { ordered, next: 0, pending: BTreeMap::new() }
+    }
+
+    fn push(&mut self, index: usize, batch: Vec<Vec<u8>>) {
+        self.pending.insert(index, batch);
+    }
+
+    // The number of processed batches that can't be written yet because an earlier one is still missing.
+    fn waiting(&self) -> usize {
+        self.pending.len()
+    }
+
+    // Get the next batch that can be written, if any.
+    fn pop(&mut self) -> Option<Vec<Vec<u8>>> {
+        if !self.ordered {
+            return self.pending.pop_first().map(|(_, batch)| batch);
+        }
+        let batch = self.pending.remove(&self.next)?;
+        self.next += 1;
+        Some(batch)
+    }
 }
 
 struct Processor<'a, W: Write> {
-    context: RenderContext<'a, W>,
-    transformations: Vec<UrlTransformation<'a>>,
+    context: RenderContext<W>,
+    sitemaps: Option<SitemapWriter>,
+    max_records: Option<usize>,
+    pipeline: Pipeline<'a>,
+    headers_written: bool,
+    record_delimiter: u8,
+    jobs: usize,
+    ordered: bool,
+    follow: bool,
+    from_start: bool,
 }
 
 impl<'a, W: Write> Processor<'a, W> {
-    fn new(context: RenderContext<'a, W>, transformations: Vec<UrlTransformation<'a>>) -> Self {
-        Self { context, transformations }
+    fn new(context: RenderContext<W>, pipeline: Pipeline<'a>, record_delimiter: u8) -> Self {
+        Self {
+            context,
+            sitemaps: None,
+            max_records: None,
+            pipeline,
+            headers_written: false,
+            record_delimiter,
+            jobs: 1,
+            ordered: true,
+            follow: false,
+            from_start: false,
+        }
     }
 
-    fn process_url(&mut self, url: &str) {
-        let url = match parse_url(url) {
-            Ok(url) => url,
-            Err(e) => {
-                exit!("Invalid URL '{url}': {e}");
-            }
-        };
-        match self.transform(url) {
-            Ok(url) => self.render(&url),
-            Err(e) => eprintln!("Error performing transformations: {e}"),
-        };
+    // Write results into sitemap files rather than into the context.
+    fn with_sitemaps(mut self, sitemaps: Option<SitemapWriter>) -> Self {
+        self.sitemaps = sitemaps;
+        self
+    }
+
+    // Fail once more than this many results are written.
+    fn with_max_records(mut self, max_records: Option<usize>) -> Self {
+        self.max_records = max_records;
+        self
+    }
+
+    // Process line based inputs using the given number of threads.
+    fn with_jobs(mut self, jobs: usize, ordered: bool) -> Self {
+        self.jobs = jobs;
+        self.ordered = ordered;
+        self
+    }
+
+    // Keep reading files as they grow, writing out every result straight away. Unless reading from the start,
+    // only what's appended to them is processed.
+    fn with_follow(mut self, follow: bool, from_start: bool) -> Self {
+        self.follow = follow;
+        self.from_start = from_start;
+        self
+    }
+
+    fn process_url(&mut self, input: &str) -> Result<(), UrlParseError> {
+        let url = parse_url(input)?;
+        if let Some(output) = self.pipeline.process_parsed_url(input, url, &Fields::new()) {
+            self.write(&output);
+        }
+        Ok(())
     }
 
     fn process_urls_file(&mut self, path: &str) {
         if path == "-" {
-            self.process_urls(stdin().lock());
-        } else {
-            match File::open(path) {
-                Ok(file) => self.process_urls(BufReader::new(file)),
-                Err(e) => {
-                    let mut cmd = Cli::command();
-                    cmd.error(ErrorKind::ValueValidation, format!("Invalid URL file path: {e}")).exit();
-                }
-            }
+            return self.process_urls(stdin().lock());
+        }
+        let result = match (self.follow, self.from_start) {
+            (true, true) => FollowReader::open_from_start(path).map(|reader| self.process_urls(BufReader::new(reader))),
+            (true, false) => FollowReader::open(path).map(|reader| self.process_urls(BufReader::new(reader))),
+            (false, _) => File::open(path).map(|file| self.process_urls(BufReader::new(file))),
+        };
+        if let Err(e) = result {
+            let mut cmd = Cli::command();
+            cmd.error(ErrorKind::ValueValidation, format!("Invalid URL file path: {e}")).exit();
         }
     }
 
     fn process_urls<R: BufRead>(&mut self, reader: R) {
-        for line in reader.lines() {
+        match self.pipeline.parser {
+            InputParser::Har => return self.process_har(reader),
+            InputParser::Delimited(options) => return self.process_delimited(reader, *options),
+            _ => (),
+        };
+        let lines = LineReader::with_delimiter(reader, self.record_delimiter);
+        if self.jobs > 1 {
+            return self.process_lines_parallel(lines);
+        }
+        for line in lines {
             match line {
-                Ok(line) => self.process_url(&line),
-                Err(e) => {
+                Ok(line) => {
+                    if let Some(output) = self.pipeline.process_line(line) {
+                        self.write(&output);
+                    }
+                }
+                Err(e) => Self::report_input_error(e),
+            };
+        }
+    }
+
+    // Lines are read and results are written in this thread, while the actual processing happens in the
+    // worker threads. Reading is throttled by the bounded line channel, and by waiting for the missing batch
+    // whenever too many processed ones are waiting on it, so only a few batches are ever held in memory even if
+    // one of them is slow to process.
+    fn process_lines_parallel<R: BufRead>(&mut self, lines: LineReader<R>) {
+        let pipeline = self.pipeline;
+        let (line_sender, line_receiver) = mpsc::sync_channel::<(usize, Vec<Line>)>(self.jobs * 2);
+        let line_receiver = Mutex::new(line_receiver);
+        let (output_sender, output_receiver) = mpsc::channel();
+        let mut batches = Batches::new(self.ordered);
+        thread::scope(|scope| {
+            for _ in 0..self.jobs {
+                let output_sender = output_sender.clone();
+                let line_receiver = &line_receiver;
+                scope.spawn(move || loop {
+                    // Don't hold the lock while processing so other workers can receive batches meanwhile.
+                    let batch = line_receiver.lock().expect("lock poisoned").recv();
+                    let Ok((index, lines)) = batch else { break };
+                    let outputs: Vec<_> = lines.into_iter().filter_map(|line| pipeline.process_line(line)).collect();
+                    if output_sender.send((index, outputs)).is_err() {
+                        break;
+                    }
+                });
+            }
+            drop(output_sender);
+
+            let mut lines = lines.peekable();
+            let mut index = 0;
+            while lines.peek().is_some() {
+                let mut batch = Vec::with_capacity(BATCH_SIZE);
+                for line in lines.by_ref().take(BATCH_SIZE) {
+                    match line {
+                        Ok(line) => batch.push(line),
+                        Err(e) => Self::report_input_error(e),
+                    };
+                }
+                if line_sender.send((index, batch)).is_err() {
+                    break;
+                }
+                index += 1;
+                for (index, outputs) in output_receiver.try_iter() {
+                    batches.push(index, outputs);
+                }
+                self.write_batches(&mut batches);
+                while batches.waiting() > self.jobs * 2 {
+                    let Ok((index, outputs)) = output_receiver.recv() else { break };
+                    batches.push(index, outputs);
+                    self.write_batches(&mut batches);
+                }
+            }
+            drop(line_sender);
+            for (index, outputs) in output_receiver {
+                batches.push(index, outputs);
+                self.write_batches(&mut batches);
+            }
+        });
+    }
+
+    fn write_batches(&mut self, batches: &mut Batches) {
+        while let Some(outputs) = batches.pop() {
+            for output in outputs {
+                self.write(&output);
+            }
+        }
+    }
+
+    fn report_input_error(error: InputError) {
+        match error {
+            InputError::Io(e) => {
+                exit!("Failed to read input: {e}");
+            }
+            e => eprintln!("Invalid input: {e}"),
+        };
+    }
+
+    fn process_har<R: Read>(&mut self, reader: R) {
+        let records = match parse_har(reader) {
+            Ok(records) => records,
+            Err(e) => {
+                exit!("Invalid HAR file: {e}");
+            }
+        };
+        for (index, record) in records.into_iter().enumerate() {
+            if let Some(output) = self.pipeline.process_record(record, format_args!("entry {}", index + 1)) {
+                self.write(&output);
+            }
+        }
+    }
+
+    fn process_delimited<R: Read>(&mut self, reader: R, options: DelimitedOptions) {
+        let DelimitedOptions { delimiter, column, has_headers, .. } = options;
+        let terminator = self.record_delimiter;
+        let reader = match DelimitedReader::with_terminator(reader, delimiter, terminator, has_headers, column) {
+            Ok(reader) => reader,
+            Err(e) => {
+                exit!("Invalid input: {e}");
+            }
+        };
+        let column = reader.column();
+        // When processing multiple files, they're all expected to have the same headers.
+        if let Some(headers) = reader.headers().filter(|_| !self.headers_written) {
+            let headers = headers.iter().map(String::as_str).chain(options.output_column);
+            self.write_row(options.delimiter, headers);
+            self.headers_written = true;
+        }
+        for row in reader {
+            let mut row = match row {
+                Ok(row) => row,
+                Err(InputError::Io(e)) => {
                     exit!("Failed to read input: {e}");
                 }
+                Err(e) => {
+                    eprintln!("Invalid row: {e}");
+                    continue;
+                }
             };
+            let output = self
+                .pipeline
+                .process_to_url(&row.values[column], format_args!("line {}", row.line))
+                .and_then(|url| self.pipeline.render_to_string(&url));
+            match (output, options.output_column) {
+                (output, Some(_)) => row.values.push(output.unwrap_or_default()),
+                (Some(output), None) => row.values[column] = output,
+                (None, None) => (),
+            };
+            self.write_row(options.delimiter, &row.values);
+        }
+    }
+
+    fn write(&mut self, output: &[u8]) {
+        if self.max_records.is_some_and(|max_records| self.context.count() == max_records) {
+            exit!("A sitemap can't have more than {MAX_SITEMAP_URLS} URLs, use `--sitemap-dir` to split it");
+        }
+        let result = match &mut self.sitemaps {
+            Some(sitemaps) => sitemaps.write(output),
+            None => self.context.write(output),
+        };
+        if let Err(e) = result {
+            exit!("Failed to write output: {e}");
         }
+        self.flush();
     }
 
-    fn transform(&self, mut url: Url) -> Result<Url, TransformError> {
-        for transformation in &self.transformations {
-            url = transformation.apply(url)?
+    fn write_row<I, S>(&mut self, delimiter: u8, values: I)
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let writer = DelimitedWriter::with_terminator(delimiter, self.record_delimiter);
+        if let Err(e) = writer.write_row(self.context.get_mut(), values) {
+            exit!("Failed to write output: {e}");
         }
-        Ok(url)
+        self.flush();
     }
 
-    fn render(&mut self, url: &Url) {
-        if let Err(e) = self.context.render(url) {
-            eprintln!("Rendering failed: {e}");
+    fn flush(&mut self) {
+        if !self.follow {
+            return;
+        }
+        if let Err(e) = self.context.get_mut().flush() {
+            exit!("Failed to write output: {e}");
+        }
+    }
+
+    // Close the output. The context is closed when dropped, but sitemaps can fail to be closed.
+    fn finish(self) {
+        if let Some(sitemaps) = self.sitemaps {
+            if let Err(e) = sitemaps.finish() {
+                exit!("Failed to write sitemaps: {e}");
+            }
         }
     }
 }
 
-fn main() {
-    let cli = Cli::parse();
-    let renderer = match cli.output_json {
-        true => UrlRenderer::json(),
-        false => UrlRenderer::templated(&cli.template),
+// Serves `POST /process` requests, whose body is a JSON encoded `ProcessRequest`.
+fn serve(args: &ServeArgs, transformations: &[UrlTransformation], renderer: &(dyn Render + Sync)) {
+    let listen = &args.listen;
+    let server = match tiny_http::Server::http(listen) {
+        Ok(server) => server,
+        Err(e) => {
+            exit!("Failed to listen on {listen}: {e}");
+        }
     };
+    eprintln!("Listening on {listen}");
+    let threads = thread::available_parallelism().map(usize::from).unwrap_or(1);
+    let content_type = tiny_http::Header::from_bytes("Content-Type", "application/json").expect("invalid header");
+    thread::scope(|scope| {
+        for _ in 0..threads {
+            scope.spawn(|| {
+                while let Ok(mut request) = server.recv() {
+                    let (status, body) = handle_request(&mut request, transformations, renderer);
+                    let response = tiny_http::Response::from_data(body)
+                        .with_status_code(status)
+                        .with_header(content_type.clone());
+                    if let Err(e) = request.respond(response) {
+                        eprintln!("Failed to send response: {e}");
+                    }
+                }
+            });
+        }
+    });
+}
+
+// The largest request body that's accepted, in bytes.
+const MAX_REQUEST_SIZE: u64 = 16 * 1024 * 1024;
+
+fn handle_request(
+    request: &mut tiny_http::Request,
+    transformations: &[UrlTransformation],
+    renderer: &(dyn Render + Sync),
+) -> (u16, String) {
+    let error = |message: String| json!({ "error": message }).to_string();
+    match (request.method(), request.url()) {
+        (tiny_http::Method::Post, "/process") => (),
+        (_, "/process") => return (405, error("method not allowed".into())),
+        _ => return (404, error("not found".into())),
+    };
+    let too_large = || (413, error(format!("request body is larger than {MAX_REQUEST_SIZE} bytes")));
+    if request.body_length().is_some_and(|length| length as u64 > MAX_REQUEST_SIZE) {
+        return too_large();
+    }
+    // The length isn't known upfront for chunked requests, so reading a byte past the limit detects it.
+    let mut body = Vec::new();
+    if let Err(e) = request.as_reader().take(MAX_REQUEST_SIZE + 1).read_to_end(&mut body) {
+        return (400, error(format!("failed to read request: {e}")));
+    }
+    if body.len() as u64 > MAX_REQUEST_SIZE {
+        return too_large();
+    }
+    let process_request: ProcessRequest = match serde_json::from_slice(&body) {
+        Ok(process_request) => process_request,
+        Err(e) => return (400, error(format!("invalid request: {e}"))),
+    };
+    let response = match &process_request.pipeline {
+        Some(pipeline) => match pipeline.transformations() {
+            Ok(transformations) => process_urls(&process_request.urls, &transformations, &pipeline.renderer()),
+            Err(e) => return (400, error(format!("invalid pipeline: {e}"))),
+        },
+        None => process_urls(&process_request.urls, transformations, renderer),
+    };
+    match serde_json::to_string(&response) {
+        Ok(response) => (200, response),
+        Err(e) => (500, error(e.to_string())),
+    }
+}
+
+fn serve_rpc() {
+    let mut stdout = io::stdout().lock();
+    for message in stdin().lock().lines() {
+        let message = match message {
+            Ok(message) => message,
+            Err(e) => {
+                exit!("Failed to read input: {e}");
+            }
+        };
+        if message.trim().is_empty() {
+            continue;
+        }
+        if let Some(response) = rpc::handle_message(&message) {
+            if let Err(e) = writeln!(stdout, "{response}").and_then(|_| stdout.flush()) {
+                exit!("Failed to write output: {e}");
+            }
+        }
+    }
+}
+
+fn explain(args: &ExplainArgs) {
+    let mut stdout = io::stdout().lock();
+    let mut failed = false;
+    let mut explain_url = |url: &str| {
+        match Explanation::new(url) {
+            Ok(explanation) => {
+                // Explanations end in a newline already, so this leaves an empty line between them.
+                if let Err(e) = writeln!(stdout, "{explanation}") {
+                    exit!("Failed to write output: {e}");
+                }
+            }
+            Err(e) => {
+                eprintln!("Invalid URL '{url}': {e}");
+                failed = true;
+            }
+        };
+    };
+    if args.urls.is_empty() {
+        for line in stdin().lock().lines() {
+            match line {
+                Ok(line) if line.trim().is_empty() => continue,
+                Ok(line) => explain_url(line.trim()),
+                Err(e) => {
+                    exit!("Failed to read input: {e}");
+                }
+            };
+        }
+    } else {
+        args.urls.iter().for_each(|url| explain_url(url));
+    }
+    if failed {
+        exit(1);
+    }
+}
+
+fn main() {
+    let matches = Cli::command().get_matches();
+    let cli = Cli::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+    let parser = cli.input_format.parser(&cli);
+    let record_delimiter = if cli.null { b'\0' } else { b'\n' };
+    let renderer = build_renderer(&cli, &parser, record_delimiter);
     let transformations = build_transformations(&cli);
+    match &cli.command {
+        Some(Command::Serve(args)) => return serve(args, &transformations, renderer.as_ref()),
+        Some(Command::Rpc) => return serve_rpc(),
+        Some(Command::Explain(args)) => return explain(args),
+        None => (),
+    };
     let stdout = io::stdout().lock();
-    let render_json_list = cli.output_json && cli.input.urls_path.is_some();
-    let context = match render_json_list {
-        true => RenderContext::new_json_list(renderer, stdout),
-        false => RenderContext::new_single_line(renderer, stdout),
+    if cli.follow && (cli.input.urls_path.len() > 1 || matches!(parser, InputParser::Har)) {
+        let mut cmd = Cli::command();
+        let message = "`--follow` can only be used with a single `--urls-path` that isn't a HAR file";
+        cmd.error(ErrorKind::ArgumentConflict, message).exit();
+    }
+    let delimited_options = cli.column.is_some() || cli.output_column.is_some() || cli.no_header;
+    if delimited_options && !matches!(parser, InputParser::Delimited(_)) {
+        let mut cmd = Cli::command();
+        let message = "`--column`, `--output-column` and `--no-header` can only be used with CSV or TSV inputs";
+        cmd.error(ErrorKind::ArgumentConflict, message).exit();
+    }
+    let whole_records = cli.changed_only || cli.mapping.is_some() || cli.redirects.is_some() || cli.sitemap.is_some();
+    if whole_records && parser.is_passthrough() {
+        let mut cmd = Cli::command();
+        let message = "`--changed-only`, `--mapping`, `--redirects` and `--sitemap` can't be used with CSV, TSV or \
+                       JSON Lines inputs";
+        cmd.error(ErrorKind::ArgumentConflict, message).exit();
+    }
+    let renderer: Box<dyn Render + Sync> = match cli.mapping {
+        Some(mapping) => Box::new(MappingRenderer::new(renderer, mapping.into()).with_terminator(record_delimiter)),
+        None => renderer,
+    };
+    // Passthrough inputs are written back record by record, so they're never framed. JSON arrays are only used
+    // when there's more than one URL, and enclosed lists are never used for followed files since the list would
+    // never be closed.
+    let framing = match renderer.list_framing() {
+        _ if parser.is_passthrough() || cli.sitemap_dir.is_some() => ListFraming::Records,
+        ListFraming::Enclosed { .. } if cli.output_json && !cli.input.is_multi_url() => ListFraming::Records,
+        ListFraming::Enclosed { .. } if cli.follow => ListFraming::Records,
+        framing => framing,
     };
-    let mut processor = Processor::new(context, transformations);
-    match (&cli.input.url, &cli.input.urls_path) {
-        (Some(url), _) => processor.process_url(url),
-        (None, Some(path)) => processor.process_urls_file(path),
-        _ => unreachable!(),
+    let context = RenderContext::new(stdout, record_delimiter).with_framing(framing);
+    let jobs = match cli.jobs {
+        0 => thread::available_parallelism().map(usize::from).unwrap_or(1),
+        jobs => jobs,
     };
+    let keep_origin = cli.changed_only
+        || cli.diff
+        || cli.mapping.is_some()
+        || cli.redirects.is_some()
+        || highlighting(&cli, &parser);
+    let pipeline = Pipeline::new(&transformations, renderer.as_ref(), &parser)
+        // URLs the transformations leave as they are would redirect to themselves.
+        .with_changed_only(cli.changed_only || cli.redirects.is_some())
+        .with_origin(keep_origin);
+    let sitemaps = cli
+        .sitemap
+        .as_ref()
+        .zip(cli.sitemap_dir.as_ref())
+        .map(|(base, directory)| SitemapWriter::new(directory, SitemapRenderer::new(base.clone())));
+    // A single sitemap written to stdout can't be split.
+    let max_records = (cli.sitemap.is_some() && sitemaps.is_none()).then_some(MAX_SITEMAP_URLS);
+    let mut processor = Processor::new(context, pipeline, record_delimiter)
+        .with_jobs(jobs, !cli.unordered)
+        .with_follow(cli.follow, cli.from_start)
+        .with_sitemaps(sitemaps)
+        .with_max_records(max_records);
+    let mut failed = false;
+    for source in cli.input.sources(&matches) {
+        match source {
+            InputSource::Url(url) => {
+                if let Err(e) = processor.process_url(url) {
+                    eprintln!("Invalid URL '{url}': {e}");
+                    failed = true;
+                }
+            }
+            InputSource::Path(path) => processor.process_urls_file(path),
+        }
+    }
+    // Make sure the output is terminated before exiting.
+    processor.finish();
+    if failed {
+        exit(1);
+    }
 }
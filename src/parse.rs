@@ -4,15 +4,24 @@ use std::borrow::Cow;
 use url::Url;
 
 /// Parse a URL.
+///
+/// URLs without a scheme, like `example.com/foo`, are parsed as if they used `http`.
 pub fn parse_url(url: &str) -> Result<Url, UrlParseError> {
-    let output = match Url::parse(url) {
+    parse_url_inferring_scheme(url).map(|(url, _)| url)
+}
+
+// Parse a URL, also returning whether its scheme was inferred rather than being part of the input.
+pub(crate) fn parse_url_inferring_scheme(url: &str) -> Result<(Url, bool), UrlParseError> {
+    let (output, mut inferred_scheme) = match Url::parse(url) {
         // If we either have a cannot-be-a-base or we failed parsing, attempt to add http as the
         // scheme.
-        Ok(url) if url.cannot_be_a_base() => Url::parse(&format!("http://{url}")),
+        Ok(url) if url.cannot_be_a_base() => (Url::parse(&format!("http://{url}")), true),
         // Attempt to prepend "http://" unless the URL starts with a slash as that would allow
         // something like "/foo" to be a valid URL.
-        Err(url::ParseError::RelativeUrlWithoutBase) if !url.starts_with('/') => Url::parse(&format!("http://{url}")),
-        other => other,
+        Err(url::ParseError::RelativeUrlWithoutBase) if !url.starts_with('/') => {
+            (Url::parse(&format!("http://{url}")), true)
+        }
+        other => (other, false),
     };
     if let Ok(url) = &output {
         // If we still have a cannot-be-a-base here we're done.
@@ -26,8 +35,9 @@ pub fn parse_url(url: &str) -> Result<Url, UrlParseError> {
                 // Based on docs this is the only scenario we can hit here but we already validated
                 // it is a base.
                 url.set_scheme("http").expect("cannot-be-a-base");
+                inferred_scheme = true;
             }
-            Ok(url)
+            Ok((url, inferred_scheme))
         }
         Err(e) => Err(UrlParseError(e.to_string().into())),
     }
@@ -1,11 +1,357 @@
 //! URL rendering.
 
-use core::fmt::Formatter;
-use runtime_format::{FormatArgs, FormatKey, FormatKeyError};
+// Adds the methods of renderers whose output is a list of keys, forwarding them to their `columns` field, which
+// is a `KeyColumns`.
+macro_rules! key_columns_methods {
+    ($lifetime:lifetime) => {
+        /// Add a custom key that can be used by this renderer.
+        ///
+        /// See [crate::UrlTemplate::with_key].
+        pub fn with_key<S, K>(mut self, name: S, key: K) -> Self
+        where
+            S: Into<String>,
+            K: $crate::render::TemplateKey + Send + Sync + $lifetime,
+        {
+            self.columns.add_key(name, key);
+            self
+        }
+
+        /// Check that every key this renderer uses is known.
+        ///
+        /// See [crate::UrlTemplate::validate].
+        pub fn validate<S: AsRef<str>>(&self, fields: &[S]) -> Result<(), $crate::render::RenderError> {
+            self.columns.validate(fields)
+        }
+
+        /// Render a URL into the given writer.
+        pub fn render<W: std::io::Write>(
+            &self,
+            url: &url::Url,
+            writer: &mut W,
+        ) -> Result<(), $crate::render::RenderError> {
+            $crate::render::Render::render_with_fields(self, url, &$crate::render::Fields::new(), writer)
+        }
+    };
+}
+
+pub mod delimited;
+pub mod diff;
+pub mod escape;
+mod highlight;
+pub mod http;
+pub mod mapping;
+pub mod redirect;
+pub mod sitemap;
+pub mod table;
+
+use crate::components::Component;
+use escape::Escape;
+use core::fmt::{self, Formatter};
+use runtime_format::{FormatArgs, FormatKey, FormatKeyError, ParsedFmt};
 use serde::Serialize;
-use std::{borrow::Cow, io::Write};
+use serde_json::Value;
+use std::{
+    borrow::Cow,
+    cell::Cell,
+    collections::HashMap,
+    fmt::Write as _,
+    io::{self, Write},
+};
 use url::Url;
 
+/// Extra values that can be rendered along with a URL.
+///
+/// These are typically extracted from the input a URL was read from, e.g. the status code in an access log
+/// line. Every field can be used as a template key and is included in the JSON output, under `fields`.
+pub type Fields = serde_json::Map<String, Value>;
+
+// The keys every template can use, other than `query:<name>`.
+const BUILTIN_KEYS: &[&str] = &["url", "scheme", "host", "port", "user", "password", "path", "query", "fragment"];
+
+// Custom keys, by name.
+type CustomKeys<'a> = HashMap<String, Box<dyn TemplateKey + Send + Sync + 'a>>;
+
+/// Provides the value of a custom template key.
+///
+/// This is implemented for any `Fn(&Url) -> String`, so closures can be used as keys.
+pub trait TemplateKey {
+    /// Get the value of this key for the given URL.
+    fn value(&self, url: &Url) -> String;
+}
+
+impl<F: Fn(&Url) -> String> TemplateKey for F {
+    fn value(&self, url: &Url) -> String {
+        self(url)
+    }
+}
+
+/// A way of rendering URLs.
+///
+/// This is implemented by [UrlRenderer] and [UrlTemplate], and can be implemented by any other type to add
+/// output formats of its own. Renderers can be used along with a [RenderContext] to frame their output when
+/// rendering a list of URLs.
+///
+/// # Example
+///
+/// ```
+/// # use std::io::Write;
+/// # use trustrl::{render::{ListFraming, Render, RenderContext}, Fields, RenderError, Url};
+/// # fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// struct HostList;
+///
+/// impl Render for HostList {
+///     fn render_with_fields(&self, url: &Url, _: &Fields, writer: &mut dyn Write) -> Result<(), RenderError> {
+///         write!(writer, "<li>{}</li>", url.host_str().unwrap_or_default())?;
+///         Ok(())
+///     }
+///
+///     fn list_framing(&self) -> ListFraming {
+///         ListFraming::Enclosed { open: "<ul>".into(), separator: "".into(), close: "</ul>".into() }
+///     }
+/// }
+///
+/// let mut output = Vec::new();
+/// let mut context = RenderContext::new(&mut output, b'\n').with_framing(HostList.list_framing());
+/// for url in ["http://a.com", "http://b.com"] {
+///     context.render(&HostList, &Url::parse(url)?, &Fields::new())?;
+/// }
+/// context.finish()?;
+/// assert_eq!(output, b"<ul><li>a.com</li><li>b.com</li></ul>\n");
+/// # Ok(())
+/// # }
+/// ```
+pub trait Render {
+    /// Render a URL along with a set of extra fields into the given writer.
+    fn render_with_fields(&self, url: &Url, fields: &Fields, writer: &mut dyn Write) -> Result<(), RenderError>;
+
+    /// Render a URL along with a set of extra fields into a JSON value.
+    ///
+    /// By default this renders the URL into a string.
+    fn render_value(&self, url: &Url, fields: &Fields) -> Result<Value, RenderError> {
+        let mut output = Vec::new();
+        self.render_with_fields(url, fields, &mut output)?;
+        Ok(Value::String(String::from_utf8_lossy(&output).into_owned()))
+    }
+
+    /// Render a URL that was produced by transforming another one, along with a set of extra fields, into the
+    /// given writer. This allows pointing out what the transformations changed.
+    ///
+    /// By default this renders the transformed URL on its own.
+    fn render_transformed(
+        &self,
+        origin: &Origin,
+        url: &Url,
+        fields: &Fields,
+        writer: &mut dyn Write,
+    ) -> Result<(), RenderError> {
+        let _ = origin;
+        self.render_with_fields(url, fields, writer)
+    }
+
+    /// Render a URL that was produced by transforming another one, along with a set of extra fields, into a JSON
+    /// value.
+    ///
+    /// By default this renders the URL into a string via [Render::render_transformed].
+    fn render_transformed_value(&self, origin: &Origin, url: &Url, fields: &Fields) -> Result<Value, RenderError> {
+        let mut output = Vec::new();
+        self.render_transformed(origin, url, fields, &mut output)?;
+        Ok(Value::String(String::from_utf8_lossy(&output).into_owned()))
+    }
+
+    /// How this renderer's output is framed when rendering a list of URLs.
+    ///
+    /// By default every URL is written as a record of its own.
+    fn list_framing(&self) -> ListFraming {
+        ListFraming::default()
+    }
+}
+
+impl<R: Render + ?Sized> Render for &R {
+    fn render_with_fields(&self, url: &Url, fields: &Fields, writer: &mut dyn Write) -> Result<(), RenderError> {
+        (**self).render_with_fields(url, fields, writer)
+    }
+
+    fn render_value(&self, url: &Url, fields: &Fields) -> Result<Value, RenderError> {
+        (**self).render_value(url, fields)
+    }
+
+    fn render_transformed(
+        &self,
+        origin: &Origin,
+        url: &Url,
+        fields: &Fields,
+        writer: &mut dyn Write,
+    ) -> Result<(), RenderError> {
+        (**self).render_transformed(origin, url, fields, writer)
+    }
+
+    fn render_transformed_value(&self, origin: &Origin, url: &Url, fields: &Fields) -> Result<Value, RenderError> {
+        (**self).render_transformed_value(origin, url, fields)
+    }
+
+    fn list_framing(&self) -> ListFraming {
+        (**self).list_framing()
+    }
+}
+
+impl<R: Render + ?Sized> Render for Box<R> {
+    fn render_with_fields(&self, url: &Url, fields: &Fields, writer: &mut dyn Write) -> Result<(), RenderError> {
+        (**self).render_with_fields(url, fields, writer)
+    }
+
+    fn render_value(&self, url: &Url, fields: &Fields) -> Result<Value, RenderError> {
+        (**self).render_value(url, fields)
+    }
+
+    fn render_transformed(
+        &self,
+        origin: &Origin,
+        url: &Url,
+        fields: &Fields,
+        writer: &mut dyn Write,
+    ) -> Result<(), RenderError> {
+        (**self).render_transformed(origin, url, fields, writer)
+    }
+
+    fn render_transformed_value(&self, origin: &Origin, url: &Url, fields: &Fields) -> Result<Value, RenderError> {
+        (**self).render_transformed_value(origin, url, fields)
+    }
+
+    fn list_framing(&self) -> ListFraming {
+        (**self).list_framing()
+    }
+}
+
+/// Where a rendered URL came from.
+#[derive(Clone, Copy, Debug)]
+pub struct Origin<'a> {
+    /// The text the URL was parsed from.
+    pub input: &'a str,
+
+    /// The URL as it was parsed, before any transformations were applied to it.
+    pub url: &'a Url,
+}
+
+/// How the output of a renderer is framed when rendering a list of URLs.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum ListFraming {
+    /// Every URL is terminated on its own.
+    #[default]
+    Records,
+
+    /// Every URL is terminated on its own, after a header record that's written even if the list is empty.
+    Header(String),
+
+    /// URLs are separated from each other and the list is enclosed, like the elements in a JSON array. The
+    /// list as a whole is terminated instead of every URL.
+    Enclosed {
+        /// Written before the first URL.
+        open: String,
+
+        /// Written between every two URLs.
+        separator: String,
+
+        /// Written after the last URL.
+        close: String,
+    },
+}
+
+/// Writes the output of a renderer, framing it as a list.
+///
+/// The list is closed when [RenderContext::finish] is called or, ignoring any errors, when the context is
+/// dropped.
+pub struct RenderContext<W: Write> {
+    writer: W,
+    framing: ListFraming,
+    terminator: u8,
+    count: usize,
+    finished: bool,
+}
+
+impl<W: Write> RenderContext<W> {
+    /// Construct a context that writes every rendered URL as a record terminated by the given byte.
+    pub fn new(writer: W, terminator: u8) -> Self {
+        Self { writer, framing: ListFraming::Records, terminator, count: 0, finished: false }
+    }
+
+    /// Set how the rendered URLs are framed.
+    pub fn with_framing(mut self, framing: ListFraming) -> Self {
+        self.framing = framing;
+        self
+    }
+
+    /// Render a URL along with a set of extra fields and write it.
+    pub fn render<R: Render + ?Sized>(&mut self, renderer: &R, url: &Url, fields: &Fields) -> Result<(), RenderError> {
+        let mut output = Vec::new();
+        renderer.render_with_fields(url, fields, &mut output)?;
+        Ok(self.write(&output)?)
+    }
+
+    /// Write an already rendered URL.
+    pub fn write(&mut self, rendered: &[u8]) -> io::Result<()> {
+        match (&self.framing, self.count) {
+            (_, 0) => self.open()?,
+            (ListFraming::Enclosed { separator, .. }, _) => self.writer.write_all(separator.as_bytes())?,
+            _ => (),
+        };
+        self.writer.write_all(rendered)?;
+        if !matches!(self.framing, ListFraming::Enclosed { .. }) {
+            self.writer.write_all(&[self.terminator])?;
+        }
+        self.count += 1;
+        Ok(())
+    }
+
+    /// The number of URLs written so far.
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    /// Get a mutable reference to the underlying writer.
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.writer
+    }
+
+    /// Close the list and flush the underlying writer.
+    pub fn finish(mut self) -> io::Result<()> {
+        self.close()?;
+        self.writer.flush()
+    }
+
+    fn open(&mut self) -> io::Result<()> {
+        match &self.framing {
+            ListFraming::Records => Ok(()),
+            ListFraming::Header(header) => {
+                self.writer.write_all(header.as_bytes())?;
+                self.writer.write_all(&[self.terminator])
+            }
+            ListFraming::Enclosed { open, .. } => self.writer.write_all(open.as_bytes()),
+        }
+    }
+
+    fn close(&mut self) -> io::Result<()> {
+        if self.finished {
+            return Ok(());
+        }
+        self.finished = true;
+        // Nothing was rendered so the list was never opened.
+        if self.count == 0 {
+            self.open()?;
+        }
+        if let ListFraming::Enclosed { close, .. } = &self.framing {
+            self.writer.write_all(close.as_bytes())?;
+            self.writer.write_all(&[self.terminator])?;
+        }
+        Ok(())
+    }
+}
+
+impl<W: Write> Drop for RenderContext<W> {
+    fn drop(&mut self) {
+        let _ = self.close();
+    }
+}
+
 /// Allows rendering URLs.
 pub enum UrlRenderer<'a> {
     /// A renderer based on a template.
@@ -45,19 +391,66 @@ impl<'a> UrlRenderer<'a> {
     /// # }
     /// ````
     pub fn render<W: Write>(&self, url: &Url, writer: &mut W) -> Result<(), RenderError> {
+        self.render_with_fields(url, &Fields::new(), writer)
+    }
+
+    /// Render a URL along with a set of extra fields into the given writer.
+    pub fn render_with_fields<W: Write>(&self, url: &Url, fields: &Fields, writer: &mut W) -> Result<(), RenderError> {
         use UrlRenderer::*;
         match self {
-            Template(template) => template.render(url, writer),
-            Json => Self::render_json(url, writer),
+            Template(template) => template.render_with_fields(url, fields, writer),
+            Json => Self::render_json(url, fields, writer),
         }
     }
 
-    fn render_json<W: Write>(url: &Url, writer: &mut W) -> Result<(), RenderError> {
-        serde_json::to_writer(writer, &JsonUrl::from(url))?;
+    fn render_json<W: Write>(url: &Url, fields: &Fields, writer: &mut W) -> Result<(), RenderError> {
+        serde_json::to_writer(writer, &JsonUrl::new(url, fields))?;
         Ok(())
     }
 }
 
+impl Render for UrlRenderer<'_> {
+    fn render_with_fields(&self, url: &Url, fields: &Fields, mut writer: &mut dyn Write) -> Result<(), RenderError> {
+        UrlRenderer::render_with_fields(self, url, fields, &mut writer)
+    }
+
+    fn render_transformed(
+        &self,
+        origin: &Origin,
+        url: &Url,
+        fields: &Fields,
+        writer: &mut dyn Write,
+    ) -> Result<(), RenderError> {
+        match self {
+            Self::Template(template) => template.render_transformed(origin, url, fields, writer),
+            Self::Json => Render::render_with_fields(self, url, fields, writer),
+        }
+    }
+
+    /// JSON-based renderers produce an object while templated ones produce a string.
+    fn render_value(&self, url: &Url, fields: &Fields) -> Result<Value, RenderError> {
+        match self {
+            Self::Template(template) => template.render_value(url, fields),
+            Self::Json => Ok(serde_json::to_value(JsonUrl::new(url, fields))?),
+        }
+    }
+
+    fn render_transformed_value(&self, origin: &Origin, url: &Url, fields: &Fields) -> Result<Value, RenderError> {
+        match self {
+            Self::Template(template) => template.render_transformed_value(origin, url, fields),
+            Self::Json => self.render_value(url, fields),
+        }
+    }
+
+    /// JSON-based renderers produce a JSON array while templated ones produce a record per URL.
+    fn list_framing(&self) -> ListFraming {
+        match self {
+            Self::Template(_) => ListFraming::Records,
+            Self::Json => ListFraming::Enclosed { open: "[".into(), separator: ", ".into(), close: "]".into() },
+        }
+    }
+}
+
 /// A URL template.
 ///
 /// This can be used to render a URL using a format string. Format strings should use a syntax like
@@ -74,25 +467,150 @@ impl<'a> UrlRenderer<'a> {
 /// * path
 /// * query
 /// * fragment
+///
+/// Custom keys can be added using [UrlTemplate::with_key]. Any extra [`Fields`] provided when rendering can be
+/// used as keys as well.
+///
+/// A key can be followed by `|` and the name of an [Escape] to escape its value, e.g. `curl {url|shell}`. This
+/// takes precedence over the escaping set via [UrlTemplate::with_escape]. Anything else after a `|` is part of
+/// the key, e.g. `{query:a|b}` is the value of the `a|b` query parameter.
 pub struct UrlTemplate<'a> {
     format: &'a str,
+    keys: CustomKeys<'a>,
+    highlight: bool,
+    escape: Escape,
 }
 
 impl<'a> UrlTemplate<'a> {
     /// Construct a new URL template.
     pub fn new(format: &'a str) -> Self {
-        Self { format }
+        Self { format, keys: HashMap::new(), highlight: false, escape: Escape::Raw }
+    }
+
+    /// Add a custom key that can be used in this template.
+    ///
+    /// Built-in keys take precedence over custom ones, and custom keys take precedence over fields.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use url::Url;
+    /// # use trustrl::UrlTemplate;
+    /// # fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let tenant = |url: &Url| url.path_segments().and_then(|mut s| s.next()).unwrap_or_default().to_string();
+    /// let template = UrlTemplate::new("{tenant} {path}").with_key("tenant", tenant);
+    ///
+    /// let mut output = Vec::new();
+    /// template.render(&Url::parse("https://example.com/acme/users")?, &mut output)?;
+    /// assert_eq!(output, b"acme /acme/users");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn with_key<S, K>(mut self, name: S, key: K) -> Self
+    where
+        S: Into<String>,
+        K: TemplateKey + Send + Sync + 'a,
+    {
+        self.keys.insert(name.into(), Box::new(key));
+        self
+    }
+
+    /// Set whether the URL components in the output are highlighted using ANSI colors, for terminals.
+    ///
+    /// Every component gets a color of its own, both in the `url` key and in the keys for single components.
+    /// When rendering a transformed URL via [Render::render_transformed], the components that changed are also
+    /// bold and underlined. Custom keys, fields and escaped values aren't highlighted.
+    pub fn with_highlighting(mut self, highlight: bool) -> Self {
+        self.highlight = highlight;
+        self
+    }
+
+    /// Set the way the values of the keys that don't specify an escaping of their own are escaped.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use url::Url;
+    /// # use trustrl::{render::escape::Escape, UrlTemplate};
+    /// # fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let template = UrlTemplate::new("<a href=\"{url}\">{path|raw}</a>").with_escape(Escape::Html);
+    ///
+    /// let mut output = Vec::new();
+    /// template.render(&Url::parse("https://example.com/a?x=1&y=\"")?, &mut output)?;
+    /// assert_eq!(output, b"<a href=\"https://example.com/a?x=1&amp;y=%22\">/a</a>");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn with_escape(mut self, escape: Escape) -> Self {
+        self.escape = escape;
+        self
+    }
+
+    /// Check that this template is well formed and that it only uses known keys.
+    ///
+    /// Known keys are the built-in ones, the ones added via [UrlTemplate::with_key] and the given field names.
+    pub fn validate<S: AsRef<str>>(&self, fields: &[S]) -> Result<(), RenderError> {
+        let format = ParsedFmt::new(self.format).map_err(|e| RenderError::Template(e.to_string()))?;
+        for key in format.keys() {
+            validate_key(key, &self.keys, fields)?;
+        }
+        Ok(())
     }
 
     /// Use this template to render a URL.
     pub fn render<W: Write>(&self, url: &Url, writer: &mut W) -> Result<(), RenderError> {
-        let formatter = UrlFormatter { url };
+        self.render_with_fields(url, &Fields::new(), writer)
+    }
+
+    /// Use this template to render a URL along with a set of extra fields.
+    pub fn render_with_fields<W: Write>(&self, url: &Url, fields: &Fields, writer: &mut W) -> Result<(), RenderError> {
+        self.render_changes(url, fields, &[], writer)
+    }
+
+    // Render a URL, pointing out the given changed components if highlighting.
+    fn render_changes<W: Write>(
+        &self,
+        url: &Url,
+        fields: &Fields,
+        changed: &[Component],
+        writer: &mut W,
+    ) -> Result<(), RenderError> {
+        let highlight = self.highlight.then_some(changed);
+        let formatter = UrlFormatter { url, keys: &self.keys, fields, highlight, escape: self.escape };
         let args = FormatArgs::new(self.format, &formatter);
-        write!(writer, "{args}")?;
+        // Format into a string first: a key error surfaces as a formatting error, which `io::Write` doesn't
+        // tolerate.
+        let mut output = String::new();
+        if write!(output, "{args}").is_err() {
+            let reason = args.status().err().map(|e| e.to_string()).unwrap_or_else(|| "formatting failed".into());
+            return Err(RenderError::Template(reason));
+        }
+        writer.write_all(output.as_bytes())?;
         Ok(())
     }
 }
 
+impl Render for UrlTemplate<'_> {
+    fn render_with_fields(&self, url: &Url, fields: &Fields, mut writer: &mut dyn Write) -> Result<(), RenderError> {
+        UrlTemplate::render_with_fields(self, url, fields, &mut writer)
+    }
+
+    /// When highlighting, the components that differ from the original URL are marked.
+    fn render_transformed(
+        &self,
+        origin: &Origin,
+        url: &Url,
+        fields: &Fields,
+        mut writer: &mut dyn Write,
+    ) -> Result<(), RenderError> {
+        let changed = match self.highlight {
+            true => Component::changed(origin.url, url),
+            false => Vec::new(),
+        };
+        self.render_changes(url, fields, &changed, &mut writer)
+    }
+}
+
 /// An error during the rendering of a URL.
 #[derive(Debug, thiserror::Error)]
 pub enum RenderError {
@@ -103,17 +621,137 @@ pub enum RenderError {
     /// JSON serialization failed.
     #[error("JSON serialization failed: {0}")]
     Json(#[from] serde_json::Error),
+
+    /// The template is invalid.
+    #[error("invalid template: {0}")]
+    Template(String),
+
+    /// The renderer can't render this URL.
+    #[error("cannot render URL: {0}")]
+    Rejected(String),
+}
+
+// Fails if a key is neither built-in, custom nor one of the given fields.
+fn validate_key<S: AsRef<str>>(key: &str, keys: &CustomKeys, fields: &[S]) -> Result<(), RenderError> {
+    let (key, _) = split_escape(key);
+    let known = BUILTIN_KEYS.contains(&key)
+        || key.split_once("query:").is_some_and(|(_, name)| !name.is_empty())
+        || keys.contains_key(key)
+        || fields.iter().any(|field| field.as_ref() == key);
+    match known {
+        true => Ok(()),
+        false => Err(RenderError::Template(format!("unknown key '{key}'"))),
+    }
 }
 
+// Split the escaping a key ends with, like in `path|shell`, from the key. Anything after the last `|` that isn't
+// the name of an escaping is part of the key, like in `query:a|b`.
+fn split_escape(key: &str) -> (&str, Option<Escape>) {
+    match key.rsplit_once('|').and_then(|(key, name)| Some((key, Escape::from_name(name)?))) {
+        Some((key, escape)) => (key, Some(escape)),
+        None => (key, None),
+    }
+}
+
+// A list of keys that are rendered separately, e.g. as the columns of a table, along with the custom keys they
+// can use. Renderers built on it get their public methods via `key_columns_methods`.
+struct KeyColumns<'a> {
+    names: Vec<String>,
+    keys: CustomKeys<'a>,
+}
+
+impl<'a> KeyColumns<'a> {
+    fn new<I, S>(names: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        Self { names: names.into_iter().map(Into::into).collect(), keys: CustomKeys::new() }
+    }
+
+    fn add_key<S, K>(&mut self, name: S, key: K)
+    where
+        S: Into<String>,
+        K: TemplateKey + Send + Sync + 'a,
+    {
+        self.keys.insert(name.into(), Box::new(key));
+    }
+
+    fn validate<S: AsRef<str>>(&self, fields: &[S]) -> Result<(), RenderError> {
+        self.names.iter().try_for_each(|name| validate_key(name, &self.keys, fields))
+    }
+
+    fn render(&self, url: &Url, fields: &Fields) -> Result<Vec<String>, RenderError> {
+        let formatter = UrlFormatter { url, keys: &self.keys, fields, highlight: None, escape: Escape::Raw };
+        self.names
+            .iter()
+            .map(|name| {
+                formatter.render_key(name).map_err(|e| match e {
+                    FormatKeyError::UnknownKey => RenderError::Template(format!("unknown key '{name}'")),
+                    e => RenderError::Template(e.to_string()),
+                })
+            })
+            .collect()
+    }
+}
+
+#[derive(Clone, Copy)]
 struct UrlFormatter<'a> {
     url: &'a Url,
+    keys: &'a CustomKeys<'a>,
+    fields: &'a Fields,
+    // The changed components, if URL components are highlighted.
+    highlight: Option<&'a [Component]>,
+    // The escaping used for keys that don't specify one.
+    escape: Escape,
 }
 
 impl<'a> UrlFormatter<'a> {
+    // Render the value of a single key.
+    fn render_key(&self, key: &str) -> Result<String, FormatKeyError> {
+        struct KeyValue<'a> {
+            formatter: &'a UrlFormatter<'a>,
+            key: &'a str,
+            // Formatting can only fail with a `fmt::Error`, so the actual error is kept here.
+            error: Cell<Option<FormatKeyError>>,
+        }
+
+        impl fmt::Display for KeyValue<'_> {
+            fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+                FormatKey::fmt(self.formatter, self.key, f).map_err(|e| {
+                    self.error.set(Some(e));
+                    fmt::Error
+                })
+            }
+        }
+
+        let value = KeyValue { formatter: self, key, error: Cell::new(None) };
+        let mut output = String::new();
+        match write!(output, "{value}") {
+            Ok(()) => Ok(output),
+            Err(e) => Err(value.error.take().unwrap_or(FormatKeyError::Fmt(e))),
+        }
+    }
+
+    fn format_key(&self, key: &str, f: &mut Formatter<'_>) -> Result<(), FormatKeyError> {
+        if key == "port" {
+            self.format_port(f)
+        } else if let Some((_, name)) = key.split_once("query:") {
+            self.format_query_parameter(name, f)
+        } else {
+            self.format_url_component(key, f)
+        }
+    }
+
     fn format_port(&self, f: &mut Formatter<'_>) -> Result<(), FormatKeyError> {
-        let output = match PortFormatter::new(self.url).port() {
-            Some(port) => write!(f, "{port}"),
-            None => write!(f, ""),
+        let port = PortFormatter::new(self.url).port().map(|port| port.to_string()).unwrap_or_default();
+        self.write_component(&port, Component::Port, f)
+    }
+
+    fn write_component(&self, value: &str, component: Component, f: &mut Formatter<'_>) -> Result<(), FormatKeyError> {
+        let output = match self.highlight {
+            Some(changed) => write!(f, "{}", highlight::paint(value, component, changed)),
+            None => write!(f, "{value}"),
         };
         output.map_err(FormatKeyError::Fmt)
     }
@@ -125,47 +763,74 @@ impl<'a> UrlFormatter<'a> {
         // Try to find this value, default to "" if not found.
         let value =
             self.url.query_pairs().find_map(|(key, value)| (key == name).then_some(value)).unwrap_or(Cow::Borrowed(""));
-        write!(f, "{}", value).map_err(FormatKeyError::Fmt)
+        self.write_component(&value, Component::Query, f)
     }
 
     fn format_url_component(&self, component: &str, f: &mut Formatter<'_>) -> Result<(), FormatKeyError> {
-        let value = match component {
-            "url" => self.url.as_str(),
-            "scheme" => self.url.scheme(),
-            "host" => self.url.host_str().unwrap_or(""),
-            "user" => self.url.username(),
-            "password" => self.url.password().unwrap_or(""),
-            "path" => self.url.path(),
-            "query" => self.url.query().unwrap_or(""),
-            "fragment" => self.url.fragment().unwrap_or(""),
-            _ => return Err(FormatKeyError::UnknownKey),
+        let (value, component) = match component {
+            "url" => return self.format_url(f),
+            "scheme" => (self.url.scheme(), Component::Scheme),
+            "host" => (self.url.host_str().unwrap_or(""), Component::Host),
+            "user" => (self.url.username(), Component::User),
+            "password" => (self.url.password().unwrap_or(""), Component::Password),
+            "path" => (self.url.path(), Component::Path),
+            "query" => (self.url.query().unwrap_or(""), Component::Query),
+            "fragment" => (self.url.fragment().unwrap_or(""), Component::Fragment),
+            _ => return self.format_custom_key(component, f),
         };
-        write!(f, "{value}").map_err(FormatKeyError::Fmt)
+        self.write_component(value, component, f)
+    }
+
+    fn format_url(&self, f: &mut Formatter<'_>) -> Result<(), FormatKeyError> {
+        let output = match self.highlight {
+            Some(changed) => write!(f, "{}", highlight::paint_url(self.url, changed)),
+            None => write!(f, "{}", self.url),
+        };
+        output.map_err(FormatKeyError::Fmt)
+    }
+
+    fn format_custom_key(&self, name: &str, f: &mut Formatter<'_>) -> Result<(), FormatKeyError> {
+        match self.keys.get(name) {
+            Some(key) => write!(f, "{}", key.value(self.url)).map_err(FormatKeyError::Fmt),
+            None => self.format_field(name, f),
+        }
+    }
+
+    fn format_field(&self, name: &str, f: &mut Formatter<'_>) -> Result<(), FormatKeyError> {
+        let output = match self.fields.get(name) {
+            Some(Value::Null) => Ok(()),
+            Some(Value::String(value)) => write!(f, "{value}"),
+            Some(value) => write!(f, "{value}"),
+            None => return Err(FormatKeyError::UnknownKey),
+        };
+        output.map_err(FormatKeyError::Fmt)
     }
 }
 
 impl<'a> FormatKey for UrlFormatter<'a> {
     fn fmt(&self, key: &str, f: &mut Formatter<'_>) -> Result<(), FormatKeyError> {
-        if key == "port" {
-            self.format_port(f)
-        } else if let Some((_, name)) = key.split_once("query:") {
-            self.format_query_parameter(name, f)
-        } else {
-            self.format_url_component(key, f)
+        let (key, escape) = split_escape(key);
+        let escape = escape.unwrap_or(self.escape);
+        if escape == Escape::Raw {
+            return self.format_key(key, f);
         }
+        // Escape codes would be escaped along with the value, so escaped values are never highlighted.
+        let formatter = UrlFormatter { highlight: None, escape: Escape::Raw, ..*self };
+        let value = formatter.render_key(key)?;
+        write!(f, "{}", escape.escape(&value)).map_err(FormatKeyError::Fmt)
     }
 }
 
-struct PortFormatter<'a> {
+pub(crate) struct PortFormatter<'a> {
     url: &'a Url,
 }
 
 impl<'a> PortFormatter<'a> {
-    fn new(url: &'a Url) -> Self {
+    pub(crate) fn new(url: &'a Url) -> Self {
         Self { url }
     }
 
-    fn port(&self) -> Option<u16> {
+    pub(crate) fn port(&self) -> Option<u16> {
         if let Some(port) = self.url.port() {
             Some(port)
         } else {
@@ -173,7 +838,8 @@ impl<'a> PortFormatter<'a> {
         }
     }
 
-    fn scheme_port(scheme: &str) -> Option<u16> {
+    // The port used by default for a scheme.
+    pub(crate) fn scheme_port(scheme: &str) -> Option<u16> {
         match scheme {
             "http" | "ws" | "rtmpt" | "rtmpte" => Some(80),
             "https" | "wss" | "rtmps" | "rtmpts" => Some(443),
@@ -213,6 +879,9 @@ struct JsonUrl<'a> {
     #[serde(skip_serializing_if = "Option::is_none")]
     query: Option<&'a str>,
     params: Vec<JsonQueryParam<'a>>,
+    // Nested so fields can't clash with the keys above.
+    #[serde(skip_serializing_if = "Fields::is_empty")]
+    fields: &'a Fields,
 }
 
 #[derive(Serialize)]
@@ -221,8 +890,8 @@ struct JsonQueryParam<'a> {
     value: Cow<'a, str>,
 }
 
-impl<'a> From<&'a Url> for JsonUrl<'a> {
-    fn from(url: &'a Url) -> Self {
+impl<'a> JsonUrl<'a> {
+    fn new(url: &'a Url, fields: &'a Fields) -> Self {
         let params: Vec<_> = url.query_pairs().map(|(key, value)| JsonQueryParam { key, value }).collect();
         let user = if url.username().is_empty() { None } else { Some(url.username()) };
         JsonUrl {
@@ -235,6 +904,7 @@ impl<'a> From<&'a Url> for JsonUrl<'a> {
             path: url.path(),
             query: url.query(),
             params,
+            fields,
         }
     }
 }
@@ -274,6 +944,54 @@ mod tests {
         assert_eq!(formatted, expected);
     }
 
+    #[rstest]
+    #[case::string("{method} {path}", "GET /hello")]
+    #[case::number("{status}", "404")]
+    #[case::null("[{referer}]", "[]")]
+    #[case::builtin_precedence("{path}", "/hello")]
+    fn template_fields(#[case] format: &str, #[case] expected: &str) {
+        let input_url = Url::parse("http://example.com/hello").expect("invalid input URL");
+        let mut fields = Fields::new();
+        fields.insert("method".into(), "GET".into());
+        fields.insert("status".into(), 404.into());
+        fields.insert("referer".into(), Value::Null);
+        fields.insert("path".into(), "/other".into());
+
+        let mut writer = Vec::new();
+        UrlRenderer::templated(format).render_with_fields(&input_url, &fields, &mut writer).expect("formatting failed");
+        assert_eq!(String::from_utf8(writer).unwrap(), expected);
+    }
+
+    #[test]
+    fn json_fields() {
+        let input_url = Url::parse("http://example.com/hello").expect("invalid input URL");
+        let mut fields = Fields::new();
+        fields.insert("status".into(), 404.into());
+        fields.insert("host".into(), "other.com".into());
+
+        let mut writer = Vec::new();
+        UrlRenderer::json().render_with_fields(&input_url, &fields, &mut writer).expect("formatting failed");
+        let output: Value = serde_json::from_slice(&writer).expect("invalid JSON");
+        assert_eq!(output["fields"]["status"], 404);
+        assert_eq!(output["fields"]["host"], "other.com");
+        assert_eq!(output["host"], "example.com");
+
+        let mut writer = Vec::new();
+        UrlRenderer::json().render(&input_url, &mut writer).expect("formatting failed");
+        let output: Value = serde_json::from_slice(&writer).expect("invalid JSON");
+        assert!(output.get("fields").is_none());
+    }
+
+    #[test]
+    fn render_values() {
+        let input_url = Url::parse("http://example.com/hello").expect("invalid input URL");
+        let value = UrlRenderer::templated("{host}").render_value(&input_url, &Fields::new()).expect("render failed");
+        assert_eq!(value, "example.com");
+
+        let value = UrlRenderer::json().render_value(&input_url, &Fields::new()).expect("render failed");
+        assert_eq!(value["path"], "/hello");
+    }
+
     #[rstest]
     #[case::unknown_key("{other}")]
     #[case::broken_format_close("{other")]
@@ -284,4 +1002,135 @@ mod tests {
         let result = render_to_string(renderer, &input_url);
         assert!(result.is_err(), "result was {result:?}");
     }
+
+    #[rstest]
+    #[case::custom("{service}", "users")]
+    #[case::custom_and_builtin("{service}{path}", "users/v1/list")]
+    #[case::builtin_precedence("{host}", "users.internal")]
+    #[case::field_precedence("{status}", "custom")]
+    fn template_keys(#[case] format: &str, #[case] expected: &str) {
+        let input_url = Url::parse("http://users.internal/v1/list").expect("invalid input URL");
+        let service = |url: &Url| url.host_str().unwrap_or_default().trim_end_matches(".internal").to_string();
+        let template = UrlTemplate::new(format)
+            .with_key("service", service)
+            .with_key("host", |_: &Url| "nope".to_string())
+            .with_key("status", |_: &Url| "custom".to_string());
+        let mut fields = Fields::new();
+        fields.insert("status".into(), 404.into());
+
+        let mut writer = Vec::new();
+        template.render_with_fields(&input_url, &fields, &mut writer).expect("formatting failed");
+        assert_eq!(String::from_utf8(writer).unwrap(), expected);
+    }
+
+    #[rstest]
+    #[case::builtin("{scheme}://{host}:{port}{path}?{query}#{fragment} {url} {user} {password}", true)]
+    #[case::query_parameter("{query:id}", true)]
+    #[case::custom("{tenant}", true)]
+    #[case::field("{status}", true)]
+    #[case::unknown_key("{other}", false)]
+    #[case::query_specific_empty("{query:}", false)]
+    #[case::broken_format("{host", false)]
+    #[case::escape("{host|shell} {query:id|json} {status|xml}", true)]
+    #[case::unknown_escape("{host|bash}", false)]
+    #[case::escape_unknown_key("{other|shell}", false)]
+    #[case::query_parameter_with_bar("{query:a|b}", true)]
+    #[case::field_with_bar("{status|code}", true)]
+    fn validate(#[case] format: &str, #[case] valid: bool) {
+        let template = UrlTemplate::new(format).with_key("tenant", |_: &Url| String::new());
+        let result = template.validate(&["status", "status|code"]);
+        assert_eq!(result.is_ok(), valid, "result was {result:?}");
+    }
+
+    fn render_list(framing: ListFraming, urls: &[&str]) -> String {
+        let renderer = UrlRenderer::templated("{host}");
+        let mut output = Vec::new();
+        let mut context = RenderContext::new(&mut output, b'\n').with_framing(framing);
+        for url in urls {
+            context.render(&renderer, &Url::parse(url).unwrap(), &Fields::new()).expect("render failed");
+        }
+        context.finish().expect("finish failed");
+        String::from_utf8(output).unwrap()
+    }
+
+    fn enclosed() -> ListFraming {
+        ListFraming::Enclosed { open: "[".into(), separator: ", ".into(), close: "]".into() }
+    }
+
+    #[rstest]
+    #[case::records(ListFraming::Records, &["http://a.com", "http://b.com"], "a.com\nb.com\n")]
+    #[case::records_empty(ListFraming::Records, &[], "")]
+    #[case::header(ListFraming::Header("host".into()), &["http://a.com"], "host\na.com\n")]
+    #[case::header_empty(ListFraming::Header("host".into()), &[], "host\n")]
+    #[case::enclosed(enclosed(), &["http://a.com", "http://b.com"], "[a.com, b.com]\n")]
+    #[case::enclosed_empty(enclosed(), &[], "[]\n")]
+    fn list_framing(#[case] framing: ListFraming, #[case] urls: &[&str], #[case] expected: &str) {
+        assert_eq!(render_list(framing, urls), expected);
+    }
+
+    #[test]
+    fn close_on_drop() {
+        let mut output = Vec::new();
+        let mut context = RenderContext::new(&mut output, b'\n').with_framing(UrlRenderer::json().list_framing());
+        context.write(b"{}").expect("write failed");
+        drop(context);
+        assert_eq!(output, b"[{}]\n");
+    }
+
+    #[rstest]
+    #[case::component("{host}", "\x1b[32mb.com\x1b[0m")]
+    #[case::changed_component("{path}", "\x1b[34;1;4m/x\x1b[0m")]
+    #[case::query_parameter("{query:a}", "\x1b[93m1\x1b[0m")]
+    #[case::empty_component("[{fragment}]", "[]")]
+    #[case::url("{url}", "\x1b[36mhttp\x1b[0m://\x1b[32mb.com\x1b[0m\x1b[34;1;4m/x\x1b[0m?\x1b[93ma=1\x1b[0m")]
+    fn highlighting(#[case] format: &str, #[case] expected: &str) {
+        let template = UrlTemplate::new(format).with_highlighting(true);
+        let original = Url::parse("http://b.com/?a=1").unwrap();
+        let url = Url::parse("http://b.com/x?a=1").unwrap();
+        let origin = Origin { input: "b.com/?a=1", url: &original };
+
+        let mut output = Vec::new();
+        template.render_transformed(&origin, &url, &Fields::new(), &mut output).expect("render failed");
+        assert_eq!(String::from_utf8(output).unwrap(), expected);
+    }
+
+    #[rstest]
+    #[case::key("curl {url|shell}", Escape::Raw, r"curl 'http://b.com/it'\''s?a=%22&b=%3C&a|b=1'")]
+    #[case::query_parameter("{query:a|json}", Escape::Raw, r#""\"""#)]
+    #[case::field("{agent|html}", Escape::Raw, "&lt;script&gt;")]
+    #[case::template("<a>{path}</a>", Escape::Xml, "<a>/it&apos;s</a>")]
+    #[case::raw_key("{agent|raw} {agent}", Escape::Html, "<script> &lt;script&gt;")]
+    #[case::query_parameter_with_bar("{query:a|b} {query:a|b|json}", Escape::Raw, r#"1 "1""#)]
+    #[case::field_with_bar("{agent|name}", Escape::Shell, "'curl'")]
+    fn escaping(#[case] format: &str, #[case] escape: Escape, #[case] expected: &str) {
+        let url = Url::parse("http://b.com/it's?a=\"&b=<&a|b=1").unwrap();
+        let mut fields = Fields::new();
+        fields.insert("agent".into(), "<script>".into());
+        fields.insert("agent|name".into(), "curl".into());
+
+        let mut output = Vec::new();
+        let template = UrlTemplate::new(format).with_escape(escape);
+        template.render_with_fields(&url, &fields, &mut output).expect("render failed");
+        assert_eq!(String::from_utf8(output).unwrap(), expected);
+    }
+
+    #[test]
+    fn escaped_values_not_highlighted() {
+        let template = UrlTemplate::new("{host|shell} {host}").with_highlighting(true);
+        let mut output = Vec::new();
+        template.render(&Url::parse("http://b.com/").unwrap(), &mut output).expect("render failed");
+        assert_eq!(String::from_utf8(output).unwrap(), "'b.com' \x1b[32mb.com\x1b[0m");
+    }
+
+    #[test]
+    fn no_highlighting() {
+        let template = UrlTemplate::new("{url}");
+        let original = Url::parse("http://b.com/").unwrap();
+        let url = Url::parse("http://b.com/x").unwrap();
+        let origin = Origin { input: "http://b.com/", url: &original };
+
+        let mut output = Vec::new();
+        template.render_transformed(&origin, &url, &Fields::new(), &mut output).expect("render failed");
+        assert_eq!(output, b"http://b.com/x");
+    }
 }
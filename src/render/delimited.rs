@@ -0,0 +1,221 @@
+//! Delimited output, like CSV or TSV.
+
+use super::{Fields, KeyColumns, ListFraming, Render, RenderError};
+use std::io::{self, Write};
+use url::Url;
+
+/// Renders every URL as a row of delimited values, like CSV or TSV.
+///
+/// Every column is a template key, e.g. `host` or `query:id`. Values are quoted as described in RFC 4180 when
+/// needed.
+///
+/// # Example
+///
+/// ```
+/// # use trustrl::{render::delimited::DelimitedRenderer, Url};
+/// # fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// let renderer = DelimitedRenderer::new(b',', ["host", "query:q"]);
+///
+/// let mut output = Vec::new();
+/// renderer.render(&Url::parse("http://example.com/?q=a,b")?, &mut output)?;
+/// assert_eq!(output, b"example.com,\"a,b\"");
+/// # Ok(())
+/// # }
+/// ```
+pub struct DelimitedRenderer<'a> {
+    columns: KeyColumns<'a>,
+    delimiter: u8,
+    terminator: u8,
+    header: bool,
+}
+
+impl<'a> DelimitedRenderer<'a> {
+    /// Construct a renderer that uses the given delimiter and columns.
+    pub fn new<I, S>(delimiter: u8, columns: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        Self { columns: KeyColumns::new(columns), delimiter, terminator: b'\n', header: false }
+    }
+
+    /// Set the byte rows are terminated with, so values that contain it are quoted.
+    pub fn with_terminator(mut self, terminator: u8) -> Self {
+        self.terminator = terminator;
+        self
+    }
+
+    /// Set whether a header row with the column names is written before the first URL.
+    pub fn with_header(mut self, header: bool) -> Self {
+        self.header = header;
+        self
+    }
+
+    key_columns_methods!('a);
+
+    fn writer(&self) -> DelimitedWriter {
+        DelimitedWriter::with_terminator(self.delimiter, self.terminator)
+    }
+}
+
+impl Render for DelimitedRenderer<'_> {
+    fn render_with_fields(&self, url: &Url, fields: &Fields, writer: &mut dyn Write) -> Result<(), RenderError> {
+        let values = self.columns.render(url, fields)?;
+        self.writer().write_values(writer, values)?;
+        Ok(())
+    }
+
+    /// The header row is part of the framing, so it's written even if there are no URLs.
+    fn list_framing(&self) -> ListFraming {
+        if !self.header {
+            return ListFraming::Records;
+        }
+        let mut header = Vec::new();
+        // Writing into a vector can't fail.
+        let _ = self.writer().write_values(&mut header, &self.columns.names);
+        ListFraming::Header(String::from_utf8_lossy(&header).into_owned())
+    }
+}
+
+/// Writes rows in a delimited format.
+///
+/// Values are quoted as described in RFC 4180: any value that contains the delimiter, a quote or a line
+/// break is surrounded by quotes, and any quotes in it are doubled.
+pub struct DelimitedWriter {
+    delimiter: u8,
+    terminator: u8,
+}
+
+impl DelimitedWriter {
+    /// Construct a new writer that uses the given delimiter and terminates rows with a newline.
+    pub fn new(delimiter: u8) -> Self {
+        Self::with_terminator(delimiter, b'\n')
+    }
+
+    /// Construct a new writer that uses the given delimiter and row terminator.
+    pub fn with_terminator(delimiter: u8, terminator: u8) -> Self {
+        Self { delimiter, terminator }
+    }
+
+    /// Write a row.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use trustrl::render::delimited::DelimitedWriter;
+    /// # fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut output = Vec::new();
+    /// DelimitedWriter::new(b',').write_row(&mut output, ["a", "b,c", "say \"hi\""])?;
+    /// assert_eq!(output, b"a,\"b,c\",\"say \"\"hi\"\"\"\n");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn write_row<W, I, S>(&self, writer: &mut W, values: I) -> io::Result<()>
+    where
+        W: Write,
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        self.write_values(writer, values)?;
+        writer.write_all(&[self.terminator])
+    }
+
+    /// Write a row's values without terminating it.
+    pub fn write_values<W, I, S>(&self, writer: &mut W, values: I) -> io::Result<()>
+    where
+        W: Write + ?Sized,
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        for (index, value) in values.into_iter().enumerate() {
+            if index > 0 {
+                writer.write_all(&[self.delimiter])?;
+            }
+            self.write_value(writer, value.as_ref())?;
+        }
+        Ok(())
+    }
+
+    fn write_value<W: Write + ?Sized>(&self, writer: &mut W, value: &str) -> io::Result<()> {
+        let needs_quotes =
+            value.bytes().any(|c| matches!(c, b'"' | b'\r' | b'\n') || c == self.delimiter || c == self.terminator);
+        if !needs_quotes {
+            return writer.write_all(value.as_bytes());
+        }
+        write!(writer, "\"{}\"", value.replace('"', "\"\""))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    fn render(renderer: &DelimitedRenderer, url: &str) -> Result<String, RenderError> {
+        let mut output = Vec::new();
+        renderer.render(&Url::parse(url).expect("invalid URL"), &mut output)?;
+        Ok(String::from_utf8(output).unwrap())
+    }
+
+    #[rstest]
+    #[case::plain(b',', "http://example.com/foo", "http,example.com,80,/foo,")]
+    #[case::comma(b',', "http://example.com/a,b?id=1", "http,example.com,80,\"/a,b\",1")]
+    #[case::quote(b',', "http://example.com/?id=%22a%22", "http,example.com,80,/,\"\"\"a\"\"\"")]
+    #[case::tab(b'\t', "http://example.com/?id=a,b", "http\texample.com\t80\t/\ta,b")]
+    fn rows(#[case] delimiter: u8, #[case] url: &str, #[case] expected: &str) {
+        let renderer = DelimitedRenderer::new(delimiter, ["scheme", "host", "port", "path", "query:id"]);
+        assert_eq!(render(&renderer, url).expect("render failed"), expected);
+    }
+
+    #[test]
+    fn fields_and_custom_keys() {
+        let renderer = DelimitedRenderer::new(b',', ["status", "service"]).with_key("service", |_: &Url| "api".into());
+        let mut fields = Fields::new();
+        fields.insert("status".into(), 404.into());
+
+        let mut output = Vec::new();
+        let url = Url::parse("http://example.com").unwrap();
+        renderer.render_with_fields(&url, &fields, &mut output).expect("render failed");
+        assert_eq!(output, b"404,api");
+    }
+
+    #[test]
+    fn header() {
+        let renderer = DelimitedRenderer::new(b',', ["host", "query:a,b"]).with_header(true);
+        assert_eq!(renderer.list_framing(), ListFraming::Header("host,\"query:a,b\"".into()));
+        assert_eq!(DelimitedRenderer::new(b',', ["host"]).list_framing(), ListFraming::Records);
+    }
+
+    #[rstest]
+    #[case::valid(&["host", "query:id", "status"], true)]
+    #[case::unknown(&["host", "potato"], false)]
+    fn validate(#[case] columns: &[&str], #[case] valid: bool) {
+        let renderer = DelimitedRenderer::new(b',', columns.iter().copied());
+        assert_eq!(renderer.validate(&["status"]).is_ok(), valid);
+    }
+
+    #[test]
+    fn unknown_column() {
+        let renderer = DelimitedRenderer::new(b',', ["potato"]);
+        assert!(render(&renderer, "http://example.com").is_err());
+    }
+
+    #[rstest]
+    #[case::plain(&["a", "b"], "a,b\n")]
+    #[case::delimiter(&["a,b", "c"], "\"a,b\",c\n")]
+    #[case::quote(&["a\"b"], "\"a\"\"b\"\n")]
+    #[case::newline(&["a\nb"], "\"a\nb\"\n")]
+    #[case::empty(&["", ""], ",\n")]
+    fn write_rows(#[case] values: &[&str], #[case] expected: &str) {
+        let mut output = Vec::new();
+        DelimitedWriter::new(b',').write_row(&mut output, values).expect("write failed");
+        assert_eq!(String::from_utf8(output).unwrap(), expected);
+    }
+
+    #[test]
+    fn write_row_terminator() {
+        let mut output = Vec::new();
+        DelimitedWriter::with_terminator(b'\t', b'\0').write_row(&mut output, ["a", "b\0c"]).expect("write failed");
+        assert_eq!(output, b"a\t\"b\0c\"\0");
+    }
+}
@@ -0,0 +1,206 @@
+//! Showing what transformations changed in a URL.
+
+use super::{Fields, Origin, Render, RenderError};
+use crate::components::Component;
+use std::{
+    borrow::Cow,
+    fmt,
+    io::{self, Write},
+};
+use url::Url;
+
+/// The difference in a single component between two URLs.
+///
+/// When displayed, the query string is compared pair by pair while every other component is compared as a
+/// whole, e.g. `query: removed utm_source, added ref=x` or `port: 80 → 8080`.
+///
+/// # Example
+///
+/// ```
+/// # use trustrl::{render::diff::ComponentDiff, Url};
+/// # fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// let before = Url::parse("http://example.com/?utm_source=mail")?;
+/// let after = Url::parse("http://example.com:8080/?ref=x")?;
+///
+/// let diffs: Vec<_> = ComponentDiff::between(&before, &after).iter().map(ToString::to_string).collect();
+/// assert_eq!(diffs, ["port: 80 → 8080", "query: removed utm_source, added ref=x"]);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ComponentDiff {
+    component: Component,
+    before: Option<String>,
+    after: Option<String>,
+}
+
+impl ComponentDiff {
+    /// Get the differences between two URLs, in the order their components appear in a URL.
+    pub fn between(before: &Url, after: &Url) -> Vec<Self> {
+        Component::changed(before, after)
+            .into_iter()
+            .map(|component| Self {
+                component,
+                before: component.value(before).map(Cow::into_owned),
+                after: component.value(after).map(Cow::into_owned),
+            })
+            .collect()
+    }
+
+    /// The component that changed.
+    pub fn component(&self) -> Component {
+        self.component
+    }
+
+    /// The component's value before the change, if it had one.
+    pub fn before(&self) -> Option<&str> {
+        self.before.as_deref()
+    }
+
+    /// The component's value after the change, if it has one.
+    pub fn after(&self) -> Option<&str> {
+        self.after.as_deref()
+    }
+
+    fn fmt_query(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let before = query_pairs(self.before());
+        let after = query_pairs(self.after());
+        let removed: Vec<_> = difference(&before, &after).into_iter().map(|(key, _)| key.as_str()).collect();
+        let added: Vec<_> = difference(&after, &before).into_iter().map(format_pair).collect();
+        let mut changes = Vec::new();
+        if !removed.is_empty() {
+            changes.push(format!("removed {}", removed.join(", ")));
+        }
+        if !added.is_empty() {
+            changes.push(format!("added {}", added.join(", ")));
+        }
+        if changes.is_empty() {
+            // The same pairs are in there, either in a different order or encoded differently.
+            changes.push(if before != after { "reordered" } else { "re-encoded" }.to_string());
+        }
+        write!(f, "{}", changes.join(", "))
+    }
+}
+
+impl fmt::Display for ComponentDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: ", self.component.name())?;
+        match (self.component, self.before(), self.after()) {
+            (Component::Query, _, _) => self.fmt_query(f),
+            (_, Some(before), Some(after)) => write!(f, "{before} → {after}"),
+            (_, None, Some(after)) => write!(f, "added {after}"),
+            (_, Some(before), None) => write!(f, "removed {before}"),
+            (_, None, None) => Ok(()),
+        }
+    }
+}
+
+fn query_pairs(query: Option<&str>) -> Vec<(String, String)> {
+    let query = query.unwrap_or_default();
+    url::form_urlencoded::parse(query.as_bytes()).map(|(key, value)| (key.into_owned(), value.into_owned())).collect()
+}
+
+// The pairs in `lhs` that aren't in `rhs`, taking into account how many times every pair shows up in each.
+fn difference<'a>(lhs: &'a [(String, String)], rhs: &[(String, String)]) -> Vec<&'a (String, String)> {
+    let mut unmatched: Vec<_> = rhs.iter().collect();
+    let mut output = Vec::new();
+    for pair in lhs {
+        match unmatched.iter().position(|other| *other == pair) {
+            Some(index) => {
+                unmatched.swap_remove(index);
+            }
+            None => output.push(pair),
+        }
+    }
+    output
+}
+
+fn format_pair((key, value): &(String, String)) -> String {
+    match value.is_empty() {
+        true => key.clone(),
+        false => format!("{key}={value}"),
+    }
+}
+
+/// Renders a transformed URL along with the one it came from and what changed between them.
+///
+/// Every URL is rendered as a block of three lines: the original URL, prefixed with `-`, the transformed one,
+/// prefixed with `+`, and the list of changes. URLs rendered without an origin are shown as unchanged.
+///
+/// # Example
+///
+/// ```
+/// # use trustrl::{render::{diff::DiffRenderer, Origin, Render}, Fields, Url};
+/// # fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// let before = Url::parse("http://example.com/?utm_source=mail")?;
+/// let after = Url::parse("http://example.com:8080/?ref=x")?;
+/// let origin = Origin { input: "example.com/?utm_source=mail", url: &before };
+///
+/// let mut output = Vec::new();
+/// DiffRenderer.render_transformed(&origin, &after, &Fields::new(), &mut output)?;
+/// let expected = "- http://example.com/?utm_source=mail\n\
+///                 + http://example.com:8080/?ref=x\n  \
+///                 port: 80 → 8080; query: removed utm_source, added ref=x\n";
+/// assert_eq!(String::from_utf8(output)?, expected);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DiffRenderer;
+
+impl DiffRenderer {
+    fn write_diff(before: &Url, after: &Url, writer: &mut dyn Write) -> io::Result<()> {
+        let diffs: Vec<_> = ComponentDiff::between(before, after).iter().map(ToString::to_string).collect();
+        let changes = match diffs.is_empty() {
+            true => "no changes".to_string(),
+            false => diffs.join("; "),
+        };
+        writeln!(writer, "- {before}")?;
+        writeln!(writer, "+ {after}")?;
+        writeln!(writer, "  {changes}")
+    }
+}
+
+impl Render for DiffRenderer {
+    fn render_with_fields(&self, url: &Url, _: &Fields, writer: &mut dyn Write) -> Result<(), RenderError> {
+        Ok(Self::write_diff(url, url, writer)?)
+    }
+
+    fn render_transformed(
+        &self,
+        origin: &Origin,
+        url: &Url,
+        _: &Fields,
+        writer: &mut dyn Write,
+    ) -> Result<(), RenderError> {
+        Ok(Self::write_diff(origin.url, url, writer)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case::scheme("http://a.com/", "https://a.com/", &["scheme: http → https", "port: 80 → 443"])]
+    #[case::added("http://a.com/", "http://me@a.com/#top", &["user: added me", "fragment: added top"])]
+    #[case::removed("http://a.com/x#top", "http://a.com/x", &["fragment: removed top"])]
+    #[case::query_pairs("http://a.com/?a=1&b=2", "http://a.com/?a=2&c", &["query: removed a, b, added a=2, c"])]
+    #[case::duplicated("http://a.com/?a=1", "http://a.com/?a=1&a=1", &["query: added a=1"])]
+    #[case::reordered("http://a.com/?b=1&a=2", "http://a.com/?a=2&b=1", &["query: reordered"])]
+    #[case::re_encoded("http://a.com/?a=%31", "http://a.com/?a=1", &["query: re-encoded"])]
+    #[case::unchanged("http://a.com/", "http://a.com:80/", &[])]
+    fn diffs(#[case] before: &str, #[case] after: &str, #[case] expected: &[&str]) {
+        let diffs = ComponentDiff::between(&Url::parse(before).unwrap(), &Url::parse(after).unwrap());
+        let diffs: Vec<_> = diffs.iter().map(ToString::to_string).collect();
+        assert_eq!(diffs, expected);
+    }
+
+    #[test]
+    fn unchanged() {
+        let mut output = Vec::new();
+        DiffRenderer.render_with_fields(&Url::parse("http://a.com").unwrap(), &Fields::new(), &mut output).unwrap();
+        assert_eq!(String::from_utf8(output).unwrap(), "- http://a.com/\n+ http://a.com/\n  no changes\n");
+    }
+}
@@ -0,0 +1,120 @@
+//! Escaping of rendered values for the context they're used in.
+
+use std::borrow::Cow;
+
+/// The way values are escaped when rendering a template.
+///
+/// Values in URLs can contain quotes, angle brackets and anything else that's meaningful in a script or a
+/// document, so they should be escaped when a template generates one. Only the values of keys are escaped,
+/// the rest of the template is written as is.
+///
+/// # Example
+///
+/// ```
+/// # use trustrl::render::escape::Escape;
+/// assert_eq!(Escape::Shell.escape("it's"), r"'it'\''s'");
+/// assert_eq!(Escape::Html.escape("a&b"), "a&amp;b");
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Escape {
+    /// Values are written as they are.
+    #[default]
+    Raw,
+
+    /// Values are single quoted for POSIX shells.
+    Shell,
+
+    /// Values are written as JSON strings, including the surrounding quotes.
+    Json,
+
+    /// Values are escaped to be used in HTML attributes, which makes them safe in HTML text as well.
+    Html,
+
+    /// Values are escaped to be used in XML attributes and text.
+    Xml,
+}
+
+impl Escape {
+    /// Find an escaping mode by its name: `raw`, `shell`, `json`, `html` or `xml`.
+    ///
+    /// These are the names used in template keys, like `{path|shell}`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        let escape = match name {
+            "raw" => Self::Raw,
+            "shell" => Self::Shell,
+            "json" => Self::Json,
+            "html" => Self::Html,
+            "xml" => Self::Xml,
+            _ => return None,
+        };
+        Some(escape)
+    }
+
+    /// Escape a value.
+    pub fn escape<'a>(&self, value: &'a str) -> Cow<'a, str> {
+        match self {
+            Self::Raw => value.into(),
+            Self::Shell => shell_quote(value).into(),
+            // Serializing a string can't fail.
+            Self::Json => serde_json::to_string(value).unwrap_or_default().into(),
+            Self::Html => escape_html(value),
+            Self::Xml => escape_xml(value),
+        }
+    }
+}
+
+// Quote a value for POSIX shells, which take everything between single quotes literally.
+pub(crate) fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', r"'\''"))
+}
+
+pub(crate) fn escape_xml(value: &str) -> Cow<'_, str> {
+    escape_markup(value, "&apos;")
+}
+
+// `&apos;` isn't defined in HTML 4, so a numeric reference is used instead.
+fn escape_html(value: &str) -> Cow<'_, str> {
+    escape_markup(value, "&#39;")
+}
+
+fn escape_markup<'a>(value: &'a str, apostrophe: &str) -> Cow<'a, str> {
+    if !value.contains(['&', '<', '>', '"', '\'']) {
+        return value.into();
+    }
+    let mut output = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => output.push_str("&amp;"),
+            '<' => output.push_str("&lt;"),
+            '>' => output.push_str("&gt;"),
+            '"' => output.push_str("&quot;"),
+            '\'' => output.push_str(apostrophe),
+            c => output.push(c),
+        }
+    }
+    output.into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case::raw(Escape::Raw, r#"<a href="x">it's & $(id)</a>"#, r#"<a href="x">it's & $(id)</a>"#)]
+    #[case::shell(Escape::Shell, "it's $(id)", r"'it'\''s $(id)'")]
+    #[case::shell_empty(Escape::Shell, "", "''")]
+    #[case::json(Escape::Json, "a\"b\\c\nd", r#""a\"b\\c\nd""#)]
+    #[case::html(Escape::Html, r#"<a href="x">it's & y</a>"#, "&lt;a href=&quot;x&quot;&gt;it&#39;s &amp; y&lt;/a&gt;")]
+    #[case::xml(Escape::Xml, "a&b<c>d\"e'f", "a&amp;b&lt;c&gt;d&quot;e&apos;f")]
+    #[case::untouched(Escape::Xml, "https://example.com/", "https://example.com/")]
+    fn escaping(#[case] escape: Escape, #[case] value: &str, #[case] expected: &str) {
+        assert_eq!(escape.escape(value), expected);
+    }
+
+    #[test]
+    fn names() {
+        assert_eq!(Escape::from_name("shell"), Some(Escape::Shell));
+        assert_eq!(Escape::from_name("Shell"), None);
+    }
+}
@@ -0,0 +1,69 @@
+//! Highlighting URL components in terminals using ANSI escape codes.
+
+use crate::components::Component;
+use url::Url;
+
+// Changed components are bold and underlined, on top of their color.
+const CHANGED: &str = "1;4";
+
+const RESET: &str = "\x1b[0m";
+
+// The color every component is highlighted with.
+fn color(component: Component) -> &'static str {
+    match component {
+        Component::Scheme => "36",
+        Component::User | Component::Password => "33",
+        Component::Host => "32",
+        Component::Port => "35",
+        Component::Path => "34",
+        Component::Query => "93",
+        Component::Fragment => "90",
+    }
+}
+
+// Highlight the value of a component. Empty values are left as is so they don't produce stray escape codes.
+pub(super) fn paint(value: &str, component: Component, changed: &[Component]) -> String {
+    if value.is_empty() {
+        return String::new();
+    }
+    match changed.contains(&component) {
+        true => format!("\x1b[{};{CHANGED}m{value}{RESET}", color(component)),
+        false => format!("\x1b[{}m{value}{RESET}", color(component)),
+    }
+}
+
+// Highlight every component in a URL, leaving the delimiters between them as they are.
+pub(super) fn paint_url(url: &Url, changed: &[Component]) -> String {
+    let url_str = url.as_str();
+    let mut output = String::new();
+    let mut position = 0;
+    for component in Component::ALL {
+        let span = component.span(url);
+        if span.is_empty() {
+            continue;
+        }
+        output.push_str(&url_str[position..span.start]);
+        output.push_str(&paint(&url_str[span.clone()], component, changed));
+        position = span.end;
+    }
+    output.push_str(&url_str[position..]);
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn url() {
+        let url = Url::parse("https://me@example.com:8080/a?b=1#c").unwrap();
+        let expected = "\x1b[36mhttps\x1b[0m://\x1b[33mme\x1b[0m@\x1b[32;1;4mexample.com\x1b[0m:\x1b[35m8080\x1b[0m\
+                        \x1b[34m/a\x1b[0m?\x1b[93mb=1\x1b[0m#\x1b[90mc\x1b[0m";
+        assert_eq!(paint_url(&url, &[Component::Host]), expected);
+    }
+
+    #[test]
+    fn empty_value() {
+        assert_eq!(paint("", Component::Query, &[Component::Query]), "");
+    }
+}
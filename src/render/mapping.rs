@@ -0,0 +1,157 @@
+//! Pairing every rendered URL with the input it came from.
+
+use super::{delimited::DelimitedWriter, Fields, ListFraming, Origin, Render, RenderError};
+use serde_json::{json, Value};
+use std::io::Write;
+use url::Url;
+
+/// How an input is paired with its output.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MappingFormat {
+    /// A row with the input and the output, separated by a tab.
+    Tsv,
+
+    /// A JSON object with `input` and `output` keys.
+    Json,
+}
+
+/// Renders the input every URL was parsed from along with the output of another renderer.
+///
+/// In JSON mappings, the output is the JSON value the wrapped renderer produces, e.g. an object when using a
+/// JSON-based [crate::UrlRenderer]. URLs rendered without an origin use the URL itself as the input.
+///
+/// # Example
+///
+/// ```
+/// # use trustrl::{render::{mapping::{MappingFormat, MappingRenderer}, Origin, Render}, Fields, Url, UrlTemplate};
+/// # fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// let renderer = MappingRenderer::new(UrlTemplate::new("{host}"), MappingFormat::Json);
+/// let url = Url::parse("http://example.com/")?;
+/// let origin = Origin { input: "example.com", url: &url };
+///
+/// let mut output = Vec::new();
+/// renderer.render_transformed(&origin, &url, &Fields::new(), &mut output)?;
+/// assert_eq!(output, br#"{"input":"example.com","output":"example.com"}"#);
+/// # Ok(())
+/// # }
+/// ```
+pub struct MappingRenderer<R> {
+    renderer: R,
+    format: MappingFormat,
+    terminator: u8,
+}
+
+impl<R: Render> MappingRenderer<R> {
+    /// Construct a renderer that pairs inputs with the output of the given renderer.
+    pub fn new(renderer: R, format: MappingFormat) -> Self {
+        Self { renderer, format, terminator: b'\n' }
+    }
+
+    /// Set the byte rows are terminated with, so TSV values that contain it are quoted.
+    pub fn with_terminator(mut self, terminator: u8) -> Self {
+        self.terminator = terminator;
+        self
+    }
+
+    fn write_mapping(&self, input: &str, output: Value, writer: &mut dyn Write) -> Result<(), RenderError> {
+        match self.format {
+            MappingFormat::Tsv => {
+                let output = match output {
+                    Value::String(output) => output,
+                    output => output.to_string(),
+                };
+                DelimitedWriter::with_terminator(b'\t', self.terminator).write_values(writer, [input, &output])?;
+            }
+            MappingFormat::Json => serde_json::to_writer(writer, &json!({"input": input, "output": output}))?,
+        };
+        Ok(())
+    }
+
+    fn render_output(&self, origin: Option<&Origin>, url: &Url, fields: &Fields) -> Result<Value, RenderError> {
+        if self.format == MappingFormat::Json {
+            return match origin {
+                Some(origin) => self.renderer.render_transformed_value(origin, url, fields),
+                None => self.renderer.render_value(url, fields),
+            };
+        }
+        let mut output = Vec::new();
+        match origin {
+            Some(origin) => self.renderer.render_transformed(origin, url, fields, &mut output)?,
+            None => self.renderer.render_with_fields(url, fields, &mut output)?,
+        };
+        Ok(Value::String(String::from_utf8_lossy(&output).into_owned()))
+    }
+}
+
+impl<R: Render> Render for MappingRenderer<R> {
+    fn render_with_fields(&self, url: &Url, fields: &Fields, writer: &mut dyn Write) -> Result<(), RenderError> {
+        let output = self.render_output(None, url, fields)?;
+        self.write_mapping(url.as_str(), output, writer)
+    }
+
+    fn render_transformed(
+        &self,
+        origin: &Origin,
+        url: &Url,
+        fields: &Fields,
+        writer: &mut dyn Write,
+    ) -> Result<(), RenderError> {
+        let output = self.render_output(Some(origin), url, fields)?;
+        self.write_mapping(origin.input, output, writer)
+    }
+
+    /// Every mapping is a record of its own, regardless of how the wrapped renderer frames its output.
+    fn list_framing(&self) -> ListFraming {
+        ListFraming::Records
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{render::diff::DiffRenderer, UrlRenderer, UrlTemplate};
+    use rstest::rstest;
+
+    fn render<R: Render>(renderer: &MappingRenderer<R>, input: &str, url: &str) -> String {
+        let url = Url::parse(url).unwrap();
+        let origin = Origin { input, url: &url };
+        let mut output = Vec::new();
+        renderer.render_transformed(&origin, &url, &Fields::new(), &mut output).expect("render failed");
+        String::from_utf8(output).unwrap()
+    }
+
+    #[rstest]
+    #[case::tsv(MappingFormat::Tsv, "example.com/a", "example.com/a\texample.com")]
+    #[case::tsv_quoted(MappingFormat::Tsv, "example.com/a\tb", "\"example.com/a\tb\"\texample.com")]
+    #[case::json(MappingFormat::Json, "example.com/a", r#"{"input":"example.com/a","output":"example.com"}"#)]
+    fn mappings(#[case] format: MappingFormat, #[case] input: &str, #[case] expected: &str) {
+        let renderer = MappingRenderer::new(UrlTemplate::new("{host}"), format);
+        assert_eq!(render(&renderer, input, "http://example.com/a"), expected);
+    }
+
+    #[test]
+    fn json_output() {
+        let renderer = MappingRenderer::new(UrlRenderer::json(), MappingFormat::Json);
+        let output: Value = serde_json::from_str(&render(&renderer, "a.com", "http://a.com/")).unwrap();
+        assert_eq!(output["output"]["host"], "a.com");
+    }
+
+    #[test]
+    fn json_output_with_origin() {
+        let renderer = MappingRenderer::new(DiffRenderer, MappingFormat::Json);
+        let url = Url::parse("https://a.com/").unwrap();
+        let origin = Origin { input: "a.com", url: &Url::parse("http://a.com/").unwrap() };
+        let mut output = Vec::new();
+        renderer.render_transformed(&origin, &url, &Fields::new(), &mut output).expect("render failed");
+        let output: Value = serde_json::from_slice(&output).unwrap();
+        assert_eq!(output["output"], "- http://a.com/\n+ https://a.com/\n  scheme: http → https; port: 80 → 443\n");
+    }
+
+    #[test]
+    fn without_origin() {
+        let renderer = MappingRenderer::new(UrlTemplate::new("{host}"), MappingFormat::Tsv);
+        let mut output = Vec::new();
+        renderer.render_with_fields(&Url::parse("http://a.com").unwrap(), &Fields::new(), &mut output).unwrap();
+        assert_eq!(output, b"http://a.com/\ta.com");
+    }
+}
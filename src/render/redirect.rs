@@ -0,0 +1,235 @@
+//! Web server redirect configurations.
+
+use super::{Fields, ListFraming, Origin, Render, RenderError};
+use percent_encoding::percent_decode_str;
+use std::{borrow::Cow, io::Write};
+use url::{Position, Url};
+
+/// The kind of redirect configuration to generate.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RedirectFormat {
+    /// Entries in an nginx `map` from the requested path and query to the URL to redirect to, meant to be used
+    /// along with `if ($redirect_uri) { return 301 $redirect_uri; }` in a `server` block.
+    NginxMap,
+
+    /// An nginx exact match `location` block that returns a 301 for every path.
+    NginxLocation,
+
+    /// Apache `Redirect 301` lines. These match the path as a prefix, so they also redirect anything under it.
+    ApacheRedirect,
+
+    /// Apache `RewriteRule` lines that return a 301 for an exact path and, if there's one, query string. These
+    /// are meant to be used in a server or virtual host context.
+    ApacheRewrite,
+
+    /// A JSON object from old to new URLs.
+    ///
+    /// Every redirect is rendered on its own, so if an old URL shows up more than once the object has duplicate
+    /// keys. Most JSON parsers keep the last one.
+    Json,
+}
+
+/// Renders the redirect from the URL a transformed URL came from to the transformed URL.
+///
+/// Only the path and query of the old URL are matched, so the configuration is meant to be used for the old URL's
+/// host. Redirects only make sense when rendering transformed URLs, so rendering a URL on its own fails, and so
+/// does rendering a URL that would redirect to itself. Redirects that need to match a query string are rejected
+/// by the formats that can't match it.
+///
+/// # Example
+///
+/// ```
+/// # use trustrl::{render::{redirect::{RedirectFormat, RedirectRenderer}, Origin, Render}, Fields, Url};
+/// # fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// let renderer = RedirectRenderer::new(RedirectFormat::ApacheRedirect);
+/// let old = Url::parse("http://example.com/old")?;
+/// let origin = Origin { input: "example.com/old", url: &old };
+///
+/// let mut output = Vec::new();
+/// renderer.render_transformed(&origin, &Url::parse("https://example.com/new")?, &Fields::new(), &mut output)?;
+/// assert_eq!(output, b"Redirect 301 /old https://example.com/new");
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct RedirectRenderer {
+    format: RedirectFormat,
+}
+
+impl RedirectRenderer {
+    /// Construct a renderer that generates the given kind of configuration.
+    pub fn new(format: RedirectFormat) -> Self {
+        Self { format }
+    }
+
+    fn redirect(&self, old: &Url, new: &Url) -> Result<String, RenderError> {
+        if old == new {
+            return Err(RenderError::Rejected(format!("'{old}' would redirect to itself")));
+        }
+        let output = match self.format {
+            RedirectFormat::NginxMap => {
+                let request_uri = &old[Position::BeforePath..Position::AfterQuery];
+                format!("    {} {};", nginx_string(request_uri)?, nginx_string(new.as_str())?)
+            }
+            RedirectFormat::NginxLocation => format!(
+                "location = {} {{\n    return 301 {};\n}}",
+                nginx_string(&decoded_path(old)?)?,
+                nginx_string(new.as_str())?
+            ),
+            RedirectFormat::ApacheRedirect => {
+                format!("Redirect 301 {} {}", apache_argument(&decoded_path(old)?), apache_argument(new.as_str()))
+            }
+            RedirectFormat::ApacheRewrite => {
+                let condition = match old.query() {
+                    Some(query) => format!("RewriteCond %{{QUERY_STRING}} {}\n", apache_regex(query)),
+                    None => String::new(),
+                };
+                let path = apache_regex(&percent_decode_str(old.path()).decode_utf8_lossy());
+                let substitution = new.as_str().replace('$', "\\$").replace('%', "\\%");
+                format!("{condition}RewriteRule {path} {} [R=301,NE,QSD,L]", apache_argument(&substitution))
+            }
+            RedirectFormat::Json => {
+                format!("{}: {}", serde_json::to_string(old.as_str())?, serde_json::to_string(new.as_str())?)
+            }
+        };
+        Ok(output)
+    }
+}
+
+impl Render for RedirectRenderer {
+    fn render_with_fields(&self, url: &Url, _: &Fields, _: &mut dyn Write) -> Result<(), RenderError> {
+        Err(RenderError::Rejected(format!("there's no URL redirecting to '{url}'")))
+    }
+
+    fn render_transformed(
+        &self,
+        origin: &Origin,
+        url: &Url,
+        _: &Fields,
+        writer: &mut dyn Write,
+    ) -> Result<(), RenderError> {
+        let redirect = self.redirect(origin.url, url)?;
+        writer.write_all(redirect.as_bytes())?;
+        Ok(())
+    }
+
+    /// nginx maps and JSON objects enclose every redirect while Apache rewrites turn the rewrite engine on first.
+    fn list_framing(&self) -> ListFraming {
+        match self.format {
+            RedirectFormat::NginxMap => ListFraming::Enclosed {
+                open: "map $request_uri $redirect_uri {\n".into(),
+                separator: "\n".into(),
+                close: "\n}".into(),
+            },
+            RedirectFormat::ApacheRewrite => ListFraming::Header("RewriteEngine On".into()),
+            RedirectFormat::Json => {
+                ListFraming::Enclosed { open: "{".into(), separator: ", ".into(), close: "}".into() }
+            }
+            RedirectFormat::NginxLocation | RedirectFormat::ApacheRedirect => ListFraming::Records,
+        }
+    }
+}
+
+// The path of a URL as matched by the directives that can't match a query string.
+fn decoded_path(url: &Url) -> Result<Cow<'_, str>, RenderError> {
+    if url.query().is_some() {
+        let reason = format!("'{url}' has a query string, which can only be matched by nginx maps and Apache rewrites");
+        return Err(RenderError::Rejected(reason));
+    }
+    Ok(percent_decode_str(url.path()).decode_utf8_lossy())
+}
+
+// Quote a value for nginx. Variables are expanded even in quoted strings and there's no way to escape them.
+fn nginx_string(value: &str) -> Result<String, RenderError> {
+    if value.contains('$') {
+        return Err(RenderError::Rejected(format!("'{value}' contains a '$', which nginx would take as a variable")));
+    }
+    Ok(format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\"")))
+}
+
+// Quote an argument for Apache if it needs it.
+fn apache_argument(value: &str) -> Cow<'_, str> {
+    match value.contains(|c: char| c.is_whitespace() || c == '"') {
+        true => format!("\"{}\"", value.replace('"', "\\\"")).into(),
+        false => value.into(),
+    }
+}
+
+// A regex that matches exactly the given value, quoted as an Apache argument.
+fn apache_regex(value: &str) -> String {
+    apache_argument(&format!("^{}$", regex::escape(value))).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    fn redirect(format: RedirectFormat, old: &str, new: &str) -> Result<String, RenderError> {
+        RedirectRenderer::new(format).redirect(&Url::parse(old).unwrap(), &Url::parse(new).unwrap())
+    }
+
+    #[rstest]
+    #[case::nginx_map(RedirectFormat::NginxMap, "    \"/a%20b?x=1\" \"https://b.com/c\";")]
+    #[case::json(RedirectFormat::Json, "\"http://a.com/a%20b?x=1\": \"https://b.com/c\"")]
+    #[case::apache_rewrite(
+        RedirectFormat::ApacheRewrite,
+        "RewriteCond %{QUERY_STRING} ^x=1$\nRewriteRule \"^/a b$\" https://b.com/c [R=301,NE,QSD,L]"
+    )]
+    fn redirects_with_query(#[case] format: RedirectFormat, #[case] expected: &str) {
+        assert_eq!(redirect(format, "http://a.com/a%20b?x=1", "https://b.com/c").expect("render failed"), expected);
+    }
+
+    #[rstest]
+    #[case::nginx_location(
+        RedirectFormat::NginxLocation,
+        "location = \"/a.b\" {\n    return 301 \"https://b.com/c%20d\";\n}"
+    )]
+    #[case::apache_redirect(RedirectFormat::ApacheRedirect, "Redirect 301 /a.b https://b.com/c%20d")]
+    #[case::apache_rewrite(
+        RedirectFormat::ApacheRewrite,
+        "RewriteRule ^/a\\.b$ https://b.com/c\\%20d [R=301,NE,QSD,L]"
+    )]
+    fn redirects(#[case] format: RedirectFormat, #[case] expected: &str) {
+        assert_eq!(redirect(format, "http://a.com/a.b", "https://b.com/c%20d").expect("render failed"), expected);
+    }
+
+    #[rstest]
+    #[case::query_in_location(RedirectFormat::NginxLocation, "http://a.com/?x=1", "http://a.com/y")]
+    #[case::query_in_redirect(RedirectFormat::ApacheRedirect, "http://a.com/?x=1", "http://a.com/y")]
+    #[case::nginx_variable(RedirectFormat::NginxMap, "http://a.com/$x", "http://a.com/y")]
+    #[case::same_url(RedirectFormat::Json, "http://a.com/", "http://a.com:80/")]
+    fn rejected(#[case] format: RedirectFormat, #[case] old: &str, #[case] new: &str) {
+        assert!(matches!(redirect(format, old, new), Err(RenderError::Rejected(_))));
+    }
+
+    #[test]
+    fn nginx_map_list() {
+        let renderer = RedirectRenderer::new(RedirectFormat::NginxMap);
+        let mut output = Vec::new();
+        let mut context = crate::render::RenderContext::new(&mut output, b'\n').with_framing(renderer.list_framing());
+        for (old, new) in [("http://a.com/a", "http://a.com/b"), ("http://a.com/c", "http://a.com/d")] {
+            let old = Url::parse(old).unwrap();
+            let mut rendered = Vec::new();
+            let origin = Origin { input: old.as_str(), url: &old };
+            renderer.render_transformed(&origin, &Url::parse(new).unwrap(), &Fields::new(), &mut rendered).unwrap();
+            context.write(&rendered).unwrap();
+        }
+        context.finish().unwrap();
+        let expected = [
+            "map $request_uri $redirect_uri {",
+            "    \"/a\" \"http://a.com/b\";",
+            "    \"/c\" \"http://a.com/d\";",
+            "}",
+        ];
+        let expected: String = expected.iter().map(|line| format!("{line}\n")).collect();
+        assert_eq!(String::from_utf8(output).unwrap(), expected);
+    }
+
+    #[test]
+    fn without_origin() {
+        let renderer = RedirectRenderer::new(RedirectFormat::Json);
+        let url = Url::parse("http://a.com").unwrap();
+        assert!(renderer.render_with_fields(&url, &Fields::new(), &mut Vec::new()).is_err());
+    }
+}
@@ -0,0 +1,266 @@
+//! Sitemaps, as described in <https://www.sitemaps.org/protocol.html>.
+
+use super::{escape::escape_xml, Fields, ListFraming, Render, RenderContext, RenderError};
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::PathBuf,
+};
+use url::Url;
+
+/// The most URLs a single sitemap can contain.
+pub const MAX_SITEMAP_URLS: usize = 50_000;
+
+const XML_DECLARATION: &str = r#"<?xml version="1.0" encoding="UTF-8"?>"#;
+const NAMESPACE: &str = "http://www.sitemaps.org/schemas/sitemap/0.9";
+
+// The name of the index written along with sitemap files.
+const INDEX_FILE_NAME: &str = "sitemap.xml";
+
+/// Renders every URL as an entry in a sitemap.
+///
+/// Sitemaps can only contain URLs with the same scheme, host and port as the location they're served from, and
+/// under its directory, so any other URLs are rejected. When rendering a list of URLs, the entries are enclosed in a
+/// `urlset` element. Use a [SitemapWriter] to split long lists into multiple sitemaps.
+///
+/// # Example
+///
+/// ```
+/// # use trustrl::{render::sitemap::SitemapRenderer, Url};
+/// # fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// let renderer = SitemapRenderer::new(Url::parse("https://example.com/")?);
+///
+/// let mut output = Vec::new();
+/// renderer.render(&Url::parse("https://example.com/?a=1&b=2")?, &mut output)?;
+/// assert_eq!(output, b"  <url>\n    <loc>https://example.com/?a=1&amp;b=2</loc>\n  </url>");
+///
+/// assert!(renderer.render(&Url::parse("https://example.org/")?, &mut Vec::new()).is_err());
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct SitemapRenderer {
+    base: Url,
+}
+
+impl SitemapRenderer {
+    /// Construct a renderer for a sitemap served from the given URL.
+    ///
+    /// Only URLs with the same origin as this one and under its directory can be rendered.
+    pub fn new(base: Url) -> Self {
+        Self { base }
+    }
+
+    /// Render a URL into the given writer.
+    pub fn render<W: Write>(&self, url: &Url, writer: &mut W) -> Result<(), RenderError> {
+        Render::render_with_fields(self, url, &Fields::new(), writer)
+    }
+
+    fn check_location(&self, url: &Url) -> Result<(), RenderError> {
+        let path = self.base.path();
+        let directory = path.rfind('/').map(|index| &path[..=index]).unwrap_or_default();
+        match url.origin() == self.base.origin() && url.path().starts_with(directory) {
+            true => Ok(()),
+            false => Err(RenderError::Rejected(format!("'{url}' can't be in a sitemap served from '{}'", self.base))),
+        }
+    }
+}
+
+impl Render for SitemapRenderer {
+    fn render_with_fields(&self, url: &Url, _: &Fields, writer: &mut dyn Write) -> Result<(), RenderError> {
+        self.check_location(url)?;
+        write!(writer, "  <url>\n    <loc>{}</loc>\n  </url>", escape_xml(url.as_str()))?;
+        Ok(())
+    }
+
+    /// URLs are enclosed in a `urlset` element.
+    fn list_framing(&self) -> ListFraming {
+        ListFraming::Enclosed {
+            open: format!("{XML_DECLARATION}\n<urlset xmlns=\"{NAMESPACE}\">\n"),
+            separator: "\n".into(),
+            close: "\n</urlset>".into(),
+        }
+    }
+}
+
+/// Writes URLs rendered by a [SitemapRenderer] into sitemap files in a directory, starting a new one every
+/// [MAX_SITEMAP_URLS] URLs.
+///
+/// Sitemaps are named `sitemap-1.xml`, `sitemap-2.xml`, etc, and are listed in a `sitemap.xml` sitemap index that's
+/// written when [SitemapWriter::finish] is called or, ignoring any errors, when the writer is dropped. Every
+/// sitemap is expected to be served from the directory of the URL the renderer was constructed with.
+pub struct SitemapWriter {
+    directory: PathBuf,
+    renderer: SitemapRenderer,
+    current: Option<RenderContext<BufWriter<File>>>,
+    max_urls: usize,
+    files: usize,
+    count: usize,
+    finished: bool,
+}
+
+impl SitemapWriter {
+    /// Construct a writer that writes sitemaps into the given directory.
+    pub fn new<P: Into<PathBuf>>(directory: P, renderer: SitemapRenderer) -> Self {
+        Self {
+            directory: directory.into(),
+            renderer,
+            current: None,
+            max_urls: MAX_SITEMAP_URLS,
+            files: 0,
+            count: 0,
+            finished: false,
+        }
+    }
+
+    /// Write an already rendered URL into the current sitemap, starting a new one if it's full.
+    pub fn write(&mut self, rendered: &[u8]) -> io::Result<()> {
+        let context = match self.current.take() {
+            Some(context) if self.count < self.max_urls => self.current.insert(context),
+            previous => self.next_file(previous)?,
+        };
+        context.write(rendered)?;
+        self.count += 1;
+        Ok(())
+    }
+
+    /// Close the current sitemap and write the sitemap index.
+    pub fn finish(mut self) -> io::Result<()> {
+        self.close()
+    }
+
+    // Close the previous sitemap, if any, and start the next one.
+    fn next_file(
+        &mut self,
+        previous: Option<RenderContext<BufWriter<File>>>,
+    ) -> io::Result<&mut RenderContext<BufWriter<File>>> {
+        if let Some(context) = previous {
+            context.finish()?;
+        }
+        self.files += 1;
+        self.count = 0;
+        let file = File::create(self.directory.join(Self::file_name(self.files)))?;
+        let context = RenderContext::new(BufWriter::new(file), b'\n').with_framing(self.renderer.list_framing());
+        Ok(self.current.insert(context))
+    }
+
+    fn close(&mut self) -> io::Result<()> {
+        if self.finished {
+            return Ok(());
+        }
+        self.finished = true;
+        if let Some(context) = self.current.take() {
+            context.finish()?;
+        }
+        let mut index = BufWriter::new(File::create(self.directory.join(INDEX_FILE_NAME))?);
+        writeln!(index, "{XML_DECLARATION}\n<sitemapindex xmlns=\"{NAMESPACE}\">")?;
+        for number in 1..=self.files {
+            let file_name = Self::file_name(number);
+            let location = self.renderer.base.join(&file_name).map_err(io::Error::other)?;
+            writeln!(index, "  <sitemap>\n    <loc>{}</loc>\n  </sitemap>", escape_xml(location.as_str()))?;
+        }
+        writeln!(index, "</sitemapindex>")?;
+        index.flush()
+    }
+
+    fn file_name(number: usize) -> String {
+        format!("sitemap-{number}.xml")
+    }
+}
+
+impl Drop for SitemapWriter {
+    fn drop(&mut self) {
+        let _ = self.close();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+    use std::fs;
+
+    fn renderer() -> SitemapRenderer {
+        SitemapRenderer::new(Url::parse("https://example.com/sitemaps/").unwrap())
+    }
+
+    fn render(url: &str) -> Vec<u8> {
+        let mut output = Vec::new();
+        renderer().render(&Url::parse(url).unwrap(), &mut output).expect("render failed");
+        output
+    }
+
+    #[test]
+    fn escaping() {
+        let expected = "  <url>\n    <loc>https://example.com/sitemaps/it&apos;s?a=1&amp;b</loc>\n  </url>";
+        assert_eq!(String::from_utf8(render("https://example.com/sitemaps/it's?a=1&b")).unwrap(), expected);
+    }
+
+    #[rstest]
+    #[case::host("https://www.example.com/sitemaps/a")]
+    #[case::scheme("http://example.com/sitemaps/a")]
+    #[case::port("https://example.com:8443/sitemaps/a")]
+    #[case::outside_directory("https://example.com/a")]
+    #[case::sibling_directory("https://example.com/sitemaps-old/a")]
+    fn wrong_location(#[case] url: &str) {
+        let result = renderer().render(&Url::parse(url).unwrap(), &mut Vec::new());
+        assert!(matches!(result, Err(RenderError::Rejected(_))));
+    }
+
+    #[rstest]
+    #[case::directory("https://example.com/sitemaps/")]
+    #[case::nested("https://example.com/sitemaps/a/b?c=d")]
+    #[case::default_port("https://example.com:443/sitemaps/a")]
+    fn right_location(#[case] url: &str) {
+        renderer().render(&Url::parse(url).unwrap(), &mut Vec::new()).expect("render failed");
+    }
+
+    #[test]
+    fn base_file() {
+        let renderer = SitemapRenderer::new(Url::parse("https://example.com/a/sitemap.xml").unwrap());
+        assert!(renderer.render(&Url::parse("https://example.com/a/b").unwrap(), &mut Vec::new()).is_ok());
+        assert!(renderer.render(&Url::parse("https://example.com/b").unwrap(), &mut Vec::new()).is_err());
+    }
+
+    #[test]
+    fn list() {
+        let renderer = renderer();
+        let mut output = Vec::new();
+        let mut context = RenderContext::new(&mut output, b'\n').with_framing(renderer.list_framing());
+        context.render(&renderer, &Url::parse("https://example.com/sitemaps/a").unwrap(), &Fields::new()).unwrap();
+        context.finish().unwrap();
+        let expected = [
+            r#"<?xml version="1.0" encoding="UTF-8"?>"#,
+            r#"<urlset xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">"#,
+            "  <url>",
+            "    <loc>https://example.com/sitemaps/a</loc>",
+            "  </url>",
+            "</urlset>",
+        ];
+        let expected: String = expected.iter().map(|line| format!("{line}\n")).collect();
+        assert_eq!(String::from_utf8(output).unwrap(), expected);
+    }
+
+    #[test]
+    fn split_files() {
+        let directory = std::env::temp_dir().join(format!("trustrl-sitemaps-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let mut writer = SitemapWriter::new(&directory, renderer());
+        writer.max_urls = 2;
+        for path in ["a", "b", "c"] {
+            writer.write(&render(&format!("https://example.com/sitemaps/{path}"))).unwrap();
+        }
+        writer.finish().unwrap();
+
+        let read = |name: &str| fs::read_to_string(directory.join(name)).unwrap();
+        let (first, second, index) = (read("sitemap-1.xml"), read("sitemap-2.xml"), read("sitemap.xml"));
+        fs::remove_dir_all(&directory).unwrap();
+
+        assert_eq!(first.matches("<url>").count(), 2);
+        assert!(second.contains("<loc>https://example.com/sitemaps/c</loc>"));
+        assert!(second.ends_with("</urlset>\n"));
+        assert!(index.contains("<loc>https://example.com/sitemaps/sitemap-1.xml</loc>"));
+        assert!(index.contains("<loc>https://example.com/sitemaps/sitemap-2.xml</loc>"));
+        assert!(index.ends_with("</sitemapindex>\n"));
+    }
+}
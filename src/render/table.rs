@@ -0,0 +1,243 @@
+//! Human readable output for terminals.
+
+use super::{Fields, KeyColumns, ListFraming, Render, RenderError};
+use std::io::{self, Write};
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+use url::Url;
+
+// The space between two columns.
+const COLUMN_SEPARATOR: &str = "  ";
+
+// The narrowest a column whose width depends on the table's width can be.
+const MIN_COLUMN_WIDTH: usize = 4;
+
+/// Renders every URL as a row of a table, with the columns lined up.
+///
+/// Every column is a template key, e.g. `host` or `query:id`. Since every URL is rendered on its own, columns
+/// have a fixed width: the ones whose values are always short, like the port, are as wide as they need to be,
+/// and the rest share whatever is left of the table's width. Values that don't fit in their column are
+/// truncated.
+///
+/// # Example
+///
+/// ```
+/// # use trustrl::{render::table::TableRenderer, Url};
+/// # fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// let renderer = TableRenderer::new(["host", "port", "path"], 40);
+///
+/// let mut output = Vec::new();
+/// renderer.render(&Url::parse("http://example.com/a/very/long/path")?, &mut output)?;
+/// assert_eq!(output, "example.com      80     /a/very/long/p…".as_bytes());
+/// # Ok(())
+/// # }
+/// ```
+pub struct TableRenderer<'a> {
+    columns: KeyColumns<'a>,
+    widths: Vec<usize>,
+}
+
+impl<'a> TableRenderer<'a> {
+    /// Construct a renderer with the given columns for a table that's at most this many characters wide.
+    pub fn new<I, S>(columns: I, width: usize) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        let columns = KeyColumns::new(columns);
+        let widths = Self::column_widths(&columns.names, width);
+        Self { columns, widths }
+    }
+
+    key_columns_methods!('a);
+
+    fn column_widths(names: &[String], width: usize) -> Vec<usize> {
+        let fixed: Vec<_> = names.iter().map(|name| Self::fixed_width(name)).collect();
+        let flexible_count = fixed.iter().filter(|width| width.is_none()).count();
+        let used = fixed.iter().flatten().sum::<usize>() + COLUMN_SEPARATOR.len() * names.len().saturating_sub(1);
+        let flexible_width = match flexible_count {
+            0 => 0,
+            count => (width.saturating_sub(used) / count).max(MIN_COLUMN_WIDTH),
+        };
+        fixed.into_iter().map(|width| width.unwrap_or(flexible_width)).collect()
+    }
+
+    // The width of the columns whose values are never longer than their name, or than a few characters.
+    fn fixed_width(name: &str) -> Option<usize> {
+        let width = match name {
+            "scheme" => 6,
+            "port" => 5,
+            _ => return None,
+        };
+        Some(width.max(name.width()))
+    }
+
+    fn write_row<W, S>(&self, writer: &mut W, values: &[S]) -> io::Result<()>
+    where
+        W: Write + ?Sized,
+        S: AsRef<str>,
+    {
+        let mut row = String::new();
+        for (value, width) in values.iter().zip(&self.widths) {
+            let cell = cell(value.as_ref(), *width);
+            let padding = width.saturating_sub(cell.width());
+            row.push_str(&format!("{cell}{:padding$}{COLUMN_SEPARATOR}", ""));
+        }
+        // Rows don't end in whitespace, even if the last columns are empty.
+        writer.write_all(row.trim_end().as_bytes())
+    }
+}
+
+impl Render for TableRenderer<'_> {
+    fn render_with_fields(&self, url: &Url, fields: &Fields, writer: &mut dyn Write) -> Result<(), RenderError> {
+        let values = self.columns.render(url, fields)?;
+        self.write_row(writer, &values)?;
+        Ok(())
+    }
+
+    /// The header row contains the column names.
+    fn list_framing(&self) -> ListFraming {
+        let mut header = Vec::new();
+        // Writing into a vector can't fail.
+        let _ = self.write_row(&mut header, &self.columns.names);
+        ListFraming::Header(String::from_utf8_lossy(&header).into_owned())
+    }
+}
+
+/// Renders every URL as a block of lines, one per component, each of them labeled with the component's name.
+///
+/// Every component is a template key, e.g. `host` or `query:id`. Every line in a block is terminated, so when
+/// rendering a list of URLs there's an empty line between blocks.
+///
+/// # Example
+///
+/// ```
+/// # use trustrl::{render::table::BlockRenderer, Url};
+/// # fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// let renderer = BlockRenderer::new(["host", "query:id"]);
+///
+/// let mut output = Vec::new();
+/// renderer.render(&Url::parse("http://example.com/?id=42")?, &mut output)?;
+/// assert_eq!(output, b"host:     example.com\nquery:id: 42\n");
+/// # Ok(())
+/// # }
+/// ```
+pub struct BlockRenderer<'a> {
+    columns: KeyColumns<'a>,
+}
+
+impl<'a> BlockRenderer<'a> {
+    /// Construct a renderer that shows the given components.
+    pub fn new<I, S>(components: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        Self { columns: KeyColumns::new(components) }
+    }
+
+    key_columns_methods!('a);
+}
+
+impl Render for BlockRenderer<'_> {
+    fn render_with_fields(&self, url: &Url, fields: &Fields, writer: &mut dyn Write) -> Result<(), RenderError> {
+        let values = self.columns.render(url, fields)?;
+        let width = self.columns.names.iter().map(|name| name.width()).max().unwrap_or_default();
+        for (name, value) in self.columns.names.iter().zip(values) {
+            let padding = width - name.width();
+            let line = format!("{name}:{:padding$} {}", "", cell(&value, usize::MAX));
+            writeln!(writer, "{}", line.trim_end())?;
+        }
+        Ok(())
+    }
+}
+
+// Make a value fit in a cell of the given width, replacing control characters so they can't mess up the
+// terminal. Widths are measured in terminal columns, so wide characters take two of them.
+fn cell(value: &str, width: usize) -> String {
+    let printable: String = value.chars().map(|c| if c.is_control() { '?' } else { c }).collect();
+    if printable.width() <= width {
+        return printable;
+    }
+    let mut output = String::new();
+    let mut used = 0;
+    for c in printable.chars() {
+        used += c.width().unwrap_or_default();
+        if used > width.saturating_sub(1) {
+            break;
+        }
+        output.push(c);
+    }
+    output.push('…');
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case::fits("hello", 5, "hello")]
+    #[case::truncated("hello world", 5, "hell…")]
+    #[case::control_characters("a\nb\x1b", 5, "a?b?")]
+    #[case::multibyte("ñandú", 4, "ñan…")]
+    #[case::wide("日本語のパス", 6, "日本…")]
+    #[case::wide_fits("日本語", 6, "日本語")]
+    fn cells(#[case] value: &str, #[case] width: usize, #[case] expected: &str) {
+        assert_eq!(cell(value, width), expected);
+    }
+
+    #[rstest]
+    #[case::shared(&["host", "path"], 30, &[14, 14])]
+    #[case::fixed(&["scheme", "host", "port", "path"], 40, &[6, 11, 5, 11])]
+    #[case::too_narrow(&["host", "path"], 5, &[MIN_COLUMN_WIDTH, MIN_COLUMN_WIDTH])]
+    fn column_widths(#[case] columns: &[&str], #[case] width: usize, #[case] expected: &[usize]) {
+        let renderer = TableRenderer::new(columns.iter().copied(), width);
+        assert_eq!(renderer.widths, expected);
+    }
+
+    #[test]
+    fn table() {
+        let renderer = TableRenderer::new(["scheme", "host", "path"], 32);
+        let mut output = Vec::new();
+        let mut context = crate::render::RenderContext::new(&mut output, b'\n').with_framing(renderer.list_framing());
+        for url in ["https://example.com/foo", "http://a.com/a/much/longer/path"] {
+            context.render(&renderer, &Url::parse(url).unwrap(), &Fields::new()).expect("render failed");
+        }
+        context.finish().expect("finish failed");
+        let expected = [
+            "scheme  host         path",
+            "https   example.com  /foo",
+            "http    a.com        /a/much/lo…",
+        ];
+        let expected: String = expected.iter().map(|row| format!("{row}\n")).collect();
+        assert_eq!(String::from_utf8(output).unwrap(), expected);
+    }
+
+    #[test]
+    fn wide_characters() {
+        let renderer = TableRenderer::new(["name", "port"], 11).with_key("name", |url: &Url| match url.path() {
+            "/short" => "日本".to_string(),
+            _ => "日本語".to_string(),
+        });
+        for (url, expected) in [("http://example.com/short", "日本  80"), ("http://example.com/long", "日…   80")] {
+            let mut output = Vec::new();
+            renderer.render(&Url::parse(url).unwrap(), &mut output).expect("render failed");
+            assert_eq!(String::from_utf8(output).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn blocks() {
+        let renderer = BlockRenderer::new(["scheme", "host", "query:id"]);
+        let mut output = Vec::new();
+        renderer.render(&Url::parse("https://example.com/?id=a%0Ab").unwrap(), &mut output).expect("render failed");
+        assert_eq!(String::from_utf8(output).unwrap(), "scheme:   https\nhost:     example.com\nquery:id: a?b\n");
+    }
+
+    #[test]
+    fn unknown_column() {
+        assert!(TableRenderer::new(["potato"], 80).validate::<&str>(&[]).is_err());
+        assert!(BlockRenderer::new(["potato"]).validate::<&str>(&[]).is_err());
+    }
+}
@@ -0,0 +1,261 @@
+//! Processing URLs on behalf of other programs.
+//!
+//! This contains the types used to describe a pipeline in a request, so that long-running processes can
+//! parse, transform and render URLs without having to be restarted for every set of rules.
+
+pub mod rpc;
+
+use crate::{parse_url, render::Render, transform, Fields, UrlRenderer, UrlTransformation};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// The definition of a pipeline: the transformations to apply on every URL and how to render them.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PipelineSpec {
+    /// The transformations to apply, in order.
+    #[serde(default)]
+    pub transformations: Vec<TransformationSpec>,
+
+    /// The template to render URLs with. URLs are rendered as JSON objects if this isn't set.
+    #[serde(default)]
+    pub template: Option<String>,
+}
+
+impl PipelineSpec {
+    /// Build the transformations in this pipeline.
+    pub fn transformations(&self) -> Result<Vec<UrlTransformation<'_>>, ServiceError> {
+        self.transformations.iter().map(TransformationSpec::build).collect()
+    }
+
+    /// Build the renderer for this pipeline.
+    pub fn renderer(&self) -> UrlRenderer<'_> {
+        match &self.template {
+            Some(template) => UrlRenderer::templated(template),
+            None => UrlRenderer::json(),
+        }
+    }
+}
+
+/// The definition of a single transformation.
+///
+/// Every variant matches the [UrlTransformation] with the same name. In JSON, transformations that take
+/// arguments are represented as objects with a single key, e.g. `{"set_scheme": "https"}`, and the ones
+/// that don't as strings, e.g. `"sort_query_string"`.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TransformationSpec {
+    /// Set the URL scheme.
+    SetScheme(String),
+
+    /// Set the URL host.
+    SetHost(String),
+
+    /// Set the URL port.
+    SetPort(u16),
+
+    /// Set the URL path.
+    SetPath(String),
+
+    /// Set the URL user.
+    SetUser(String),
+
+    /// Set the URL password.
+    SetPassword(Option<String>),
+
+    /// Set the URL fragment.
+    SetFragment(Option<String>),
+
+    /// Redirect to a new path.
+    Redirect(String),
+
+    /// Append a new segment to the end of the path.
+    AppendPath(String),
+
+    /// Append a new query string key/value pair.
+    AppendQueryString {
+        /// The key to append.
+        key: String,
+
+        /// The value to append.
+        #[serde(default)]
+        value: String,
+    },
+
+    /// Sort the query string.
+    SortQueryString,
+
+    /// Reset the query string.
+    ClearQueryString,
+
+    /// Keep only the query string keys that match these regexes.
+    AllowQueryString(Vec<String>),
+
+    /// Remove the the query string keys that match these regexes.
+    DenyQueryString(Vec<String>),
+}
+
+impl TransformationSpec {
+    /// Build the transformation this describes.
+    pub fn build(&self) -> Result<UrlTransformation<'_>, ServiceError> {
+        use TransformationSpec::*;
+        let transformation = match self {
+            SetScheme(scheme) => UrlTransformation::SetScheme(scheme),
+            SetHost(host) => UrlTransformation::SetHost(host),
+            SetPort(port) => UrlTransformation::SetPort(*port),
+            SetPath(path) => UrlTransformation::SetPath(path),
+            SetUser(user) => UrlTransformation::SetUser(user),
+            SetPassword(password) => UrlTransformation::SetPassword(password.as_deref()),
+            SetFragment(fragment) => UrlTransformation::SetFragment(fragment.as_deref()),
+            Redirect(path) => UrlTransformation::Redirect(path),
+            AppendPath(path) => UrlTransformation::AppendPath(path),
+            AppendQueryString { key, value } => UrlTransformation::AppendQueryString(key, value),
+            SortQueryString => UrlTransformation::SortQueryString,
+            ClearQueryString => UrlTransformation::ClearQueryString,
+            AllowQueryString(patterns) => UrlTransformation::AllowQueryString(Self::build_regexes(patterns)?),
+            DenyQueryString(patterns) => UrlTransformation::DenyQueryString(Self::build_regexes(patterns)?),
+        };
+        Ok(transformation)
+    }
+
+    fn build_regexes(patterns: &[String]) -> Result<Vec<Regex>, ServiceError> {
+        patterns
+            .iter()
+            .map(|pattern| Regex::new(pattern).map_err(|e| ServiceError::Regex(pattern.clone(), e)))
+            .collect()
+    }
+}
+
+/// A request to process a list of URLs.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProcessRequest {
+    /// The URLs to process.
+    pub urls: Vec<String>,
+
+    /// The pipeline to process URLs with. The service's own pipeline is used if this isn't set.
+    #[serde(default)]
+    pub pipeline: Option<PipelineSpec>,
+}
+
+/// The result of processing a URL.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct ProcessResult {
+    /// The URL as it was provided.
+    pub input: String,
+
+    /// The rendered URL, if processing it succeeded. This is a string when rendering using a template and an
+    /// object otherwise.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output: Option<Value>,
+
+    /// The reason why processing the URL failed, if it did.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// The response to a [ProcessRequest].
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct ProcessResponse {
+    /// The result for every URL in the request, in the same order.
+    pub results: Vec<ProcessResult>,
+}
+
+/// Parse, transform and render a list of URLs.
+///
+/// A URL that fails to be processed doesn't stop the rest from being processed: its result contains the
+/// reason why it failed instead.
+///
+/// # Example
+///
+/// ```
+/// # use serde_json::json;
+/// # use trustrl::{service::process_urls, UrlRenderer, UrlTransformation};
+/// let transformations = [UrlTransformation::SetScheme("https")];
+/// let response = process_urls(&["example.com/foo"], &transformations, &UrlRenderer::templated("{url}"));
+/// assert_eq!(response.results[0].output, Some(json!("https://example.com/foo")));
+/// ```
+pub fn process_urls<S, R>(urls: &[S], transformations: &[UrlTransformation], renderer: &R) -> ProcessResponse
+where
+    S: AsRef<str>,
+    R: Render + ?Sized,
+{
+    let results = urls
+        .iter()
+        .map(|input| {
+            let input = input.as_ref();
+            let (output, error) = match process_url(input, transformations, renderer) {
+                Ok(output) => (Some(output), None),
+                Err(e) => (None, Some(e)),
+            };
+            ProcessResult { input: input.to_string(), output, error }
+        })
+        .collect();
+    ProcessResponse { results }
+}
+
+fn process_url<R>(input: &str, transformations: &[UrlTransformation], renderer: &R) -> Result<Value, String>
+where
+    R: Render + ?Sized,
+{
+    let url = parse_url(input).map_err(|e| format!("invalid URL: {e}"))?;
+    let url = transform::apply_all(transformations, url).map_err(|e| e.to_string())?;
+    renderer.render_value(&url, &Fields::new()).map_err(|e| e.to_string())
+}
+
+/// An error in a request.
+#[derive(Debug, thiserror::Error)]
+pub enum ServiceError {
+    /// A regex in a transformation is invalid.
+    #[error("invalid regex '{0}': {1}")]
+    Regex(String, regex::Error),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+    use serde_json::json;
+
+    #[rstest]
+    #[case::with_argument(json!({"set_scheme": "https"}), TransformationSpec::SetScheme("https".into()))]
+    #[case::without_argument(json!("sort_query_string"), TransformationSpec::SortQueryString)]
+    #[case::no_password(json!({"set_password": null}), TransformationSpec::SetPassword(None))]
+    #[case::query_string(
+        json!({"append_query_string": {"key": "a"}}),
+        TransformationSpec::AppendQueryString { key: "a".into(), value: "".into() }
+    )]
+    fn parse_transformations(#[case] input: Value, #[case] expected: TransformationSpec) {
+        let spec: TransformationSpec = serde_json::from_value(input).expect("parse failed");
+        assert_eq!(spec, expected);
+    }
+
+    #[test]
+    fn process_request() {
+        let request: ProcessRequest = serde_json::from_value(json!({
+            "urls": ["example.com/?b=1&a=2", "http://[invalid"],
+            "pipeline": {"transformations": [{"set_port": 8080}, "sort_query_string"], "template": "{port} {query}"},
+        }))
+        .expect("parse failed");
+        let pipeline = request.pipeline.expect("no pipeline");
+        let transformations = pipeline.transformations().expect("invalid pipeline");
+        let response = process_urls(&request.urls, &transformations, &pipeline.renderer());
+        assert_eq!(response.results[0].output, Some(json!("8080 a=2&b=1")));
+        assert!(response.results[1].error.is_some(), "result was {:?}", response.results[1]);
+    }
+
+    #[test]
+    fn json_output() {
+        let response = process_urls(&["http://example.com"], &[], &PipelineSpec::default().renderer());
+        let output = response.results[0].output.as_ref().expect("no output");
+        assert_eq!(output["host"], json!("example.com"));
+    }
+
+    #[test]
+    fn invalid_regex() {
+        let transformations = vec![TransformationSpec::AllowQueryString(vec!["(".into()])];
+        let pipeline = PipelineSpec { transformations, template: None };
+        assert!(pipeline.transformations().is_err());
+    }
+}
@@ -0,0 +1,277 @@
+//! JSON-RPC 2.0 requests.
+//!
+//! Every request is a JSON-RPC call to one of these methods:
+//! * `parse`, with a `url` parameter: returns the URL as a JSON object, like `--to-json` does.
+//! * `transform`, with `url` and `transformations` parameters: returns the transformed URL.
+//! * `render`, with `url` and `template` parameters: returns the URL rendered using the template.
+//! * `compare`, with `left` and `right` parameters: returns whether both URLs are equal once parsed, along
+//!   with the components that differ between them.
+
+use super::{ServiceError, TransformationSpec};
+use crate::{components::Component, parse_url, render::Render, transform, Fields, UrlRenderer, Url};
+use serde::{de::DeserializeOwned, Deserialize, Deserializer};
+use serde_json::{json, Value};
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+// The start of the range reserved for implementation defined errors.
+const SERVER_ERROR: i64 = -32000;
+
+#[derive(Deserialize)]
+struct Request {
+    jsonrpc: String,
+    // Only requests without an id are notifications, a null id still gets a response.
+    #[serde(default, deserialize_with = "present")]
+    id: Option<Value>,
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+fn present<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Value>, D::Error> {
+    Value::deserialize(deserializer).map(Some)
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ParseParams {
+    url: String,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TransformParams {
+    url: String,
+    #[serde(default)]
+    transformations: Vec<TransformationSpec>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RenderParams {
+    url: String,
+    template: String,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CompareParams {
+    left: String,
+    right: String,
+}
+
+struct RpcError {
+    code: i64,
+    message: String,
+}
+
+impl RpcError {
+    fn new<S: Into<String>>(code: i64, message: S) -> Self {
+        Self { code, message: message.into() }
+    }
+}
+
+impl From<ServiceError> for RpcError {
+    fn from(error: ServiceError) -> Self {
+        Self::new(INVALID_PARAMS, error.to_string())
+    }
+}
+
+/// Handle a JSON-RPC message, which can contain either a single request or a batch of them.
+///
+/// Returns the response that should be sent back, if any. Notifications, meaning requests without an id,
+/// are processed but don't get a response.
+///
+/// # Example
+///
+/// ```
+/// # use serde_json::json;
+/// # use trustrl::service::rpc::handle_message;
+/// let message = json!({
+///     "jsonrpc": "2.0",
+///     "id": 1,
+///     "method": "render",
+///     "params": {"url": "example.com", "template": "{port}"},
+/// });
+/// let response = handle_message(&message.to_string());
+/// assert_eq!(response, Some(json!({"jsonrpc": "2.0", "id": 1, "result": "80"})));
+/// ```
+pub fn handle_message(message: &str) -> Option<Value> {
+    let message: Value = match serde_json::from_str(message) {
+        Ok(message) => message,
+        Err(e) => return Some(error_response(Value::Null, RpcError::new(PARSE_ERROR, e.to_string()))),
+    };
+    match message {
+        Value::Array(requests) if requests.is_empty() => {
+            Some(error_response(Value::Null, RpcError::new(INVALID_REQUEST, "empty batch")))
+        }
+        Value::Array(requests) => {
+            let responses: Vec<_> = requests.into_iter().filter_map(handle_request).collect();
+            (!responses.is_empty()).then_some(Value::Array(responses))
+        }
+        request => handle_request(request),
+    }
+}
+
+fn handle_request(request: Value) -> Option<Value> {
+    let request = match serde_json::from_value::<Request>(request) {
+        Ok(request) if request.jsonrpc == "2.0" => request,
+        Ok(request) => {
+            let error = RpcError::new(INVALID_REQUEST, "unsupported JSON-RPC version");
+            return Some(error_response(request.id.unwrap_or_default(), error));
+        }
+        Err(e) => return Some(error_response(Value::Null, RpcError::new(INVALID_REQUEST, e.to_string()))),
+    };
+    let result = call(&request.method, request.params);
+    let id = request.id?;
+    let response = match result {
+        Ok(result) => json!({"jsonrpc": "2.0", "id": id, "result": result}),
+        Err(e) => error_response(id, e),
+    };
+    Some(response)
+}
+
+fn error_response(id: Value, error: RpcError) -> Value {
+    json!({"jsonrpc": "2.0", "id": id, "error": {"code": error.code, "message": error.message}})
+}
+
+fn call(method: &str, params: Value) -> Result<Value, RpcError> {
+    match method {
+        "parse" => {
+            let params: ParseParams = parse_params(params)?;
+            render_value(&UrlRenderer::json(), &parse(&params.url)?)
+        }
+        "transform" => {
+            let params: TransformParams = parse_params(params)?;
+            let transformations =
+                params.transformations.iter().map(TransformationSpec::build).collect::<Result<Vec<_>, _>>()?;
+            let url = transform::apply_all(&transformations, parse(&params.url)?)
+                .map_err(|e| RpcError::new(SERVER_ERROR, e.to_string()))?;
+            Ok(Value::String(url.into()))
+        }
+        "render" => {
+            let params: RenderParams = parse_params(params)?;
+            // Bound so the renderer, which borrows the template, is dropped before `params`.
+            let renderer = UrlRenderer::templated(&params.template);
+            render_value(&renderer, &parse(&params.url)?)
+        }
+        "compare" => {
+            let params: CompareParams = parse_params(params)?;
+            Ok(compare(&parse(&params.left)?, &parse(&params.right)?))
+        }
+        _ => Err(RpcError::new(METHOD_NOT_FOUND, format!("unknown method '{method}'"))),
+    }
+}
+
+fn parse_params<T: DeserializeOwned>(params: Value) -> Result<T, RpcError> {
+    serde_json::from_value(params).map_err(|e| RpcError::new(INVALID_PARAMS, e.to_string()))
+}
+
+fn parse(url: &str) -> Result<Url, RpcError> {
+    parse_url(url).map_err(|e| RpcError::new(SERVER_ERROR, format!("invalid URL '{url}': {e}")))
+}
+
+fn render_value(renderer: &UrlRenderer, url: &Url) -> Result<Value, RpcError> {
+    renderer.render_value(url, &Fields::new()).map_err(|e| RpcError::new(SERVER_ERROR, e.to_string()))
+}
+
+fn compare(left: &Url, right: &Url) -> Value {
+    let differences: Vec<_> = Component::changed(left, right).iter().map(Component::name).collect();
+    json!({"equal": differences.is_empty(), "differences": differences})
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    fn call(method: &str, params: Value) -> Value {
+        let request = json!({"jsonrpc": "2.0", "id": 1, "method": method, "params": params});
+        handle_message(&request.to_string()).expect("no response")
+    }
+
+    #[rstest]
+    #[case::parse("parse", json!({"url": "example.com:8080"}), json!("http://example.com:8080/"), "/url")]
+    #[case::transform(
+        "transform",
+        json!({"url": "example.com/?b=1&a=2", "transformations": [{"set_scheme": "https"}, "sort_query_string"]}),
+        json!("https://example.com/?a=2&b=1"),
+        ""
+    )]
+    #[case::render(
+        "render",
+        json!({"url": "example.com/foo", "template": "{host}{path}"}),
+        json!("example.com/foo"),
+        ""
+    )]
+    #[case::compare_equal(
+        "compare",
+        json!({"left": "example.com", "right": "http://EXAMPLE.com:80/"}),
+        json!(true),
+        "/equal"
+    )]
+    #[case::compare_different(
+        "compare",
+        json!({"left": "example.com/a", "right": "https://example.com/b"}),
+        json!(["scheme", "port", "path"]),
+        "/differences"
+    )]
+    fn methods(#[case] method: &str, #[case] params: Value, #[case] expected: Value, #[case] pointer: &str) {
+        let response = call(method, params);
+        let result = response["result"].pointer(pointer);
+        assert_eq!(result, Some(&expected), "response was {response}");
+    }
+
+    #[rstest]
+    #[case::unknown_method("potato", json!({}), METHOD_NOT_FOUND)]
+    #[case::missing_params("parse", json!({}), INVALID_PARAMS)]
+    #[case::unknown_params("parse", json!({"url": "a.com", "potato": 1}), INVALID_PARAMS)]
+    #[case::invalid_url("parse", json!({"url": "http://[x"}), SERVER_ERROR)]
+    #[case::invalid_regex(
+        "transform",
+        json!({"url": "a.com", "transformations": [{"allow_query_string": ["("]}]}),
+        INVALID_PARAMS
+    )]
+    fn method_errors(#[case] method: &str, #[case] params: Value, #[case] code: i64) {
+        let response = call(method, params);
+        assert_eq!(response["error"]["code"], json!(code), "response was {response}");
+    }
+
+    #[rstest]
+    #[case::not_json("{", PARSE_ERROR)]
+    #[case::not_a_request("42", INVALID_REQUEST)]
+    #[case::wrong_version(r#"{"jsonrpc": "1.0", "id": 1, "method": "parse"}"#, INVALID_REQUEST)]
+    #[case::empty_batch("[]", INVALID_REQUEST)]
+    fn invalid_messages(#[case] message: &str, #[case] code: i64) {
+        let response = handle_message(message).expect("no response");
+        assert_eq!(response["error"]["code"], json!(code), "response was {response}");
+    }
+
+    #[test]
+    fn batch() {
+        let message = json!([
+            {"jsonrpc": "2.0", "id": 1, "method": "render", "params": {"url": "a.com", "template": "{host}"}},
+            {"jsonrpc": "2.0", "method": "render", "params": {"url": "b.com", "template": "{host}"}},
+            {"jsonrpc": "2.0", "id": 2, "method": "render", "params": {"url": "c.com", "template": "{host}"}},
+        ]);
+        let response = handle_message(&message.to_string()).expect("no response");
+        let results: Vec<_> = response.as_array().expect("not a batch").iter().map(|r| r["result"].clone()).collect();
+        assert_eq!(results, &[json!("a.com"), json!("c.com")]);
+    }
+
+    #[test]
+    fn notification() {
+        let message = json!({"jsonrpc": "2.0", "method": "parse", "params": {"url": "a.com"}});
+        assert_eq!(handle_message(&message.to_string()), None);
+    }
+
+    #[test]
+    fn null_id() {
+        let params = json!({"url": "a.com", "template": "{host}"});
+        let message = json!({"jsonrpc": "2.0", "id": null, "method": "render", "params": params});
+        let response = handle_message(&message.to_string());
+        assert_eq!(response, Some(json!({"jsonrpc": "2.0", "id": null, "result": "a.com"})));
+    }
+}
@@ -1,5 +1,6 @@
 //! URL transformations.
 
+use crate::components::UrlComponents;
 use regex::Regex;
 use url::{ParseError, Url};
 
@@ -59,6 +60,8 @@ pub enum UrlTransformation<'a> {
 impl<'a> UrlTransformation<'a> {
     /// Apply the transformation on the given URL.
     ///
+    /// When applying several transformations, [apply_all] is cheaper than calling this on each of them.
+    ///
     /// # Example
     ///
     /// ```
@@ -72,110 +75,149 @@ impl<'a> UrlTransformation<'a> {
     /// # Ok(())
     /// # }
     /// ```
-    pub fn apply(&self, mut url: Url) -> Result<Url, TransformError> {
+    pub fn apply(&self, url: Url) -> Result<Url, TransformError> {
+        apply_all(std::slice::from_ref(self), url)
+    }
+
+    fn check_userinfo_and_port(url: &UrlComponents, name: &'static str) -> Result<(), TransformError> {
+        match url.can_have_userinfo_and_port() {
+            true => Ok(()),
+            false => Err(TransformError::Transform(name)),
+        }
+    }
+
+    fn check_base(url: &UrlComponents, name: &'static str) -> Result<(), TransformError> {
+        match url.cannot_be_a_base() {
+            true => Err(TransformError::Transform(name)),
+            false => Ok(()),
+        }
+    }
+}
+
+/// A transformation that can be applied to a URL's components.
+///
+/// This is implemented by [UrlTransformation] and can be implemented by any other type to add custom
+/// transformations. Any mix of them can be applied in order using [apply_all].
+///
+/// # Example
+///
+/// ```
+/// # use std::collections::HashMap;
+/// # use trustrl::{components::UrlComponents, transform::{apply_all, Transformation}, Url};
+/// # use trustrl::{TransformError, UrlTransformation};
+/// # fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// struct HostLookup(HashMap<&'static str, &'static str>);
+///
+/// impl Transformation for HostLookup {
+///     fn apply_to(&self, url: &mut UrlComponents) -> Result<(), TransformError> {
+///         let host = url.host().and_then(|host| self.0.get(host));
+///         let host = host.ok_or_else(|| TransformError::Custom("unknown host".into()))?;
+///         url.set_host(host).map_err(|e| TransformError::Parse("host", e))
+///     }
+/// }
+///
+/// let lookup = HostLookup(HashMap::from([("api", "api.internal.example.com")]));
+/// let transformations: [&dyn Transformation; 2] = [&lookup, &UrlTransformation::SetScheme("https")];
+/// let url = apply_all(&transformations, Url::parse("http://api/users")?)?;
+/// assert_eq!(url.as_str(), "https://api.internal.example.com/users");
+/// # Ok(())
+/// # }
+/// ```
+pub trait Transformation {
+    /// Apply the transformation on the given URL components.
+    fn apply_to(&self, url: &mut UrlComponents) -> Result<(), TransformError>;
+}
+
+impl<T: Transformation + ?Sized> Transformation for &T {
+    fn apply_to(&self, url: &mut UrlComponents) -> Result<(), TransformError> {
+        (**self).apply_to(url)
+    }
+}
+
+impl<T: Transformation + ?Sized> Transformation for Box<T> {
+    fn apply_to(&self, url: &mut UrlComponents) -> Result<(), TransformError> {
+        (**self).apply_to(url)
+    }
+}
+
+impl Transformation for UrlTransformation<'_> {
+    fn apply_to(&self, url: &mut UrlComponents) -> Result<(), TransformError> {
         use TransformError::*;
         use UrlTransformation::*;
         match self {
-            SetScheme(scheme) => url = Self::set_scheme(url, scheme)?,
-            SetHost(host) => {
-                url.set_host(Some(host)).map_err(|e| Parse("host", e))?;
+            SetScheme(scheme) => {
+                if !url.set_scheme(scheme) {
+                    return Err(Transform("scheme"));
+                }
             }
+            SetHost(host) => url.set_host(host).map_err(|e| Parse("host", e))?,
             SetPort(port) => {
-                url.set_port(Some(*port)).map_err(|_| Transform("port"))?;
-            }
-            SetPath(path) => {
-                url.set_path(path);
+                UrlTransformation::check_userinfo_and_port(url, "port")?;
+                url.set_port(Some(*port));
             }
+            SetPath(path) => url.set_path(path),
             SetUser(user) => {
-                url.set_username(user).map_err(|_| Transform("user"))?;
+                UrlTransformation::check_userinfo_and_port(url, "user")?;
+                url.set_username(user);
             }
             SetPassword(password) => {
-                url.set_password(*password).map_err(|_| Transform("password"))?;
-            }
-            SetFragment(fragment) => {
-                url.set_fragment(*fragment);
+                UrlTransformation::check_userinfo_and_port(url, "password")?;
+                url.set_password(*password);
             }
+            SetFragment(fragment) => url.set_fragment(*fragment),
             Redirect(path) => {
                 if path.as_bytes().first() == Some(&b'/') {
                     url.set_path(path)
                 } else {
-                    let mut segments = url.path_segments_mut().map_err(|_| Transform("redirect"))?;
-                    segments.pop();
-                    segments.push(path);
+                    UrlTransformation::check_base(url, "redirect")?;
+                    url.pop_path_segment();
+                    url.push_path_segment(path);
                 }
             }
             AppendPath(path) => {
-                let mut segments = url.path_segments_mut().map_err(|_| Transform("append-path"))?;
-                segments.push(path);
-                drop(segments);
-            }
-            AppendQueryString(name, value) => {
-                url.query_pairs_mut().append_pair(name, value);
-            }
-            SortQueryString => url = QueryStringMutator::Sort.mutate(url),
-            ClearQueryString => {
-                url.set_query(None);
+                UrlTransformation::check_base(url, "append-path")?;
+                url.push_path_segment(path);
             }
+            AppendQueryString(name, value) => url.append_query_pair(name, value),
+            SortQueryString => url.query_pairs_mut().sort(),
+            ClearQueryString => url.clear_query(),
             AllowQueryString(regexes) => {
-                url = QueryStringMutator::Allowlist(regexes).mutate(url);
+                url.query_pairs_mut().retain(|(key, _)| regexes.iter().any(|r| r.is_match(key)));
             }
             DenyQueryString(regexes) => {
-                url = QueryStringMutator::Denylist(regexes).mutate(url);
+                url.query_pairs_mut().retain(|(key, _)| !regexes.iter().any(|r| r.is_match(key)));
             }
         };
-        Ok(url)
-    }
-
-    fn set_scheme(mut url: Url, scheme: &str) -> Result<Url, TransformError> {
-        if url.set_scheme(scheme).is_ok() {
-            return Ok(url);
-        }
-        // `Url::set_scheme` is very picky about which scheme transitions are valid. So if the initial
-        // attempt to set the scheme fails, we replace it by hand and re-parse the URL.
-        use TransformError::Transform;
-        let url = url.to_string();
-        let rest = url.split_once(':').ok_or(Transform("scheme"))?.1;
-        let url = format!("{scheme}:{rest}");
-        Url::parse(&url).map_err(|_| Transform("scheme"))
+        Ok(())
     }
 }
 
-enum QueryStringMutator<'a> {
-    Sort,
-    Allowlist(&'a [Regex]),
-    Denylist(&'a [Regex]),
-}
-
-impl<'a> QueryStringMutator<'a> {
-    fn mutate(&self, mut url: Url) -> Url {
-        let mut key_values: Vec<_> = url.query_pairs().into_owned().collect();
-        use QueryStringMutator::*;
-        let key_values = match self {
-            Sort => {
-                key_values.sort();
-                key_values
-            }
-            Allowlist(regexes) => Self::apply_allowlist(regexes, key_values),
-            Denylist(regexes) => Self::apply_denylist(regexes, key_values),
-        };
-        // This otherwise creates an empty query string.
-        if key_values.is_empty() {
-            url.set_query(None);
-            return url;
-        }
-        url.query_pairs_mut().clear().extend_pairs(key_values.into_iter()).finish();
-        url
-    }
-
-    fn apply_allowlist(regexes: &[Regex], mut key_values: Vec<(String, String)>) -> Vec<(String, String)> {
-        key_values.retain(|(key, _)| regexes.iter().any(|r| r.is_match(key)));
-        key_values
+/// Apply a list of transformations on a URL, in order.
+///
+/// The URL is split into its components once, every transformation edits them, and the URL is put back
+/// together at the end.
+///
+/// # Example
+///
+/// ```
+/// # use url::Url;
+/// # use trustrl::transform::{apply_all, UrlTransformation};
+/// # fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// let url = Url::parse("http://example.com/?b=1&a=2")?;
+/// let transformations = [UrlTransformation::SetScheme("https"), UrlTransformation::SortQueryString];
+/// assert_eq!(apply_all(&transformations, url)?.as_str(), "https://example.com/?a=2&b=1");
+/// # Ok(())
+/// # }
+/// ```
+pub fn apply_all<T: Transformation>(transformations: &[T], url: Url) -> Result<Url, TransformError> {
+    if transformations.is_empty() {
+        return Ok(url);
     }
-
-    fn apply_denylist(regexes: &[Regex], mut key_values: Vec<(String, String)>) -> Vec<(String, String)> {
-        key_values.retain(|(key, _)| !regexes.iter().any(|r| r.is_match(key)));
-        key_values
+    let mut components = UrlComponents::from(&url);
+    for transformation in transformations {
+        transformation.apply_to(&mut components)?;
     }
+    components.to_url().map_err(|e| TransformError::Parse("url", e))
 }
 
 /// An error during the application of a transformation.
@@ -188,6 +230,10 @@ pub enum TransformError {
     /// Something that we parsed failed. e.g. a hostname.
     #[error("parsing '{0}' failed: {1}")]
     Parse(&'static str, ParseError),
+
+    /// A custom transformation failed.
+    #[error(transparent)]
+    Custom(Box<dyn std::error::Error + Send + Sync>),
 }
 
 #[cfg(test)]
@@ -210,7 +256,10 @@ mod tests {
     #[case::no_fragment(ClearQueryString, "http://foo.com/hello?a=1&b=2#id", "http://foo.com/hello#id")]
     #[case::redirect_relative(Redirect("potato"), "http://foo.com/bar/zar", "http://foo.com/bar/potato")]
     #[case::redirect_absolute(Redirect("/potato"), "http://foo.com/bar/zar", "http://foo.com/potato")]
+    #[case::host_opaque(SetHost("EX_AMPLE.com"), "potato://foo.com/a", "potato://EX_AMPLE.com/a")]
+    #[case::redirect_empty_path(Redirect("x"), "potato://foo.com", "potato://foo.com/x")]
     #[case::append_path(AppendPath("potato"), "http://foo.com/bar", "http://foo.com/bar/potato")]
+    #[case::append_path_empty_path(AppendPath("x"), "potato://foo.com", "potato://foo.com/x")]
     #[case::append_path_urlencode(
         AppendPath("potato nuggets"),
         "http://foo.com/bar",
@@ -245,4 +294,37 @@ mod tests {
         let transformed_url = transformation.apply(input_url).expect("transformation failed");
         assert_eq!(transformed_url.to_string(), expected_url, "failed for {transformation:?}");
     }
+
+    struct UppercasePath;
+
+    impl Transformation for UppercasePath {
+        fn apply_to(&self, url: &mut UrlComponents) -> Result<(), TransformError> {
+            let path = url.path().to_uppercase();
+            url.set_path(&path);
+            Ok(())
+        }
+    }
+
+    struct Fail;
+
+    impl Transformation for Fail {
+        fn apply_to(&self, _url: &mut UrlComponents) -> Result<(), TransformError> {
+            Err(TransformError::Custom("nope".into()))
+        }
+    }
+
+    #[test]
+    fn custom_transformations() {
+        let transformations: Vec<Box<dyn Transformation>> =
+            vec![Box::new(AppendPath("potato")), Box::new(UppercasePath), Box::new(SetScheme("https"))];
+        let url = apply_all(&transformations, Url::parse("http://foo.com/bar").unwrap()).expect("transform failed");
+        assert_eq!(url.as_str(), "https://foo.com/BAR/POTATO");
+    }
+
+    #[test]
+    fn custom_transformation_error() {
+        let transformations: [&dyn Transformation; 2] = [&Fail, &SetScheme("https")];
+        let error = apply_all(&transformations, Url::parse("http://foo.com").unwrap()).unwrap_err();
+        assert_eq!(error.to_string(), "nope");
+    }
 }